@@ -0,0 +1,342 @@
+//! 检查样式文件，一次报告所有问题及其所在的行列
+
+use super::{
+    docx,
+    header_footer::{self, HEADER_FOOTER_KEYS},
+    section::{self, PAGE_KEYS, PAGE_NUMBER_KEYS},
+    style::{self, is_builtin, BUILTIN_STYLE_IDS, SETTING_TABLES, STYLE_KEYS},
+};
+use std::{fs::read_to_string, ops::Range, path::PathBuf};
+use toml::Value as Val;
+use toml_edit::ImDocument;
+
+/// 样式文件中的一个问题
+struct Problem {
+    /// 出问题的键路径
+    path: Vec<String>,
+    /// 指向键本身而不是键的值
+    on_key: bool,
+    message: String,
+    help: Option<String>,
+    error: bool,
+}
+
+/// 检查样式文件并打印所有问题，有错误时返回 `false`
+///
+/// `base` 是样式文件之下的预设，其中的自定义样式也可以被引用。
+pub fn check_files(files: &[PathBuf], base: &toml::Table) -> bool {
+    let mut ok = true;
+    let mut parsed = Vec::new();
+    for path in files {
+        let text = match read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("{}: error: Failed to read style file: {e}", path.display());
+                ok = false;
+                continue;
+            }
+        };
+        match ImDocument::parse(text.clone()) {
+            Ok(doc) => parsed.push((path, text.parse::<toml::Table>().unwrap(), doc)),
+            Err(e) => {
+                eprint!("{}: error: {e}", path.display());
+                ok = false;
+            }
+        }
+    }
+
+    // 被引用的样式可以定义在任何一个文件中
+    let mut styles = (1..=9)
+        .map(|depth| format!("Heading{depth}"))
+        .chain(BUILTIN_STYLE_IDS.iter().map(|&id| id.into()))
+        .collect::<Vec<_>>();
+    for table in std::iter::once(base).chain(parsed.iter().map(|(_, table, _)| table)) {
+        styles.extend(
+            table
+                .iter()
+                .filter(|(id, val)| val.is_table() && !is_builtin(id))
+                .map(|(id, _)| id.clone()),
+        );
+    }
+
+    // 各项设置由转换时也使用的解析函数检查，不改变转换的状态
+    for (path, table, doc) in &parsed {
+        // 按在文件中出现的顺序报告
+        let mut problems = check(table, &styles)
+            .into_iter()
+            .map(|p| (locate(doc, &p.path, p.on_key).map(|span| span.start), p))
+            .collect::<Vec<_>>();
+        problems.sort_by_key(|(at, _)| *at);
+        for (at, problem) in problems {
+            ok &= !problem.error;
+            let at = at
+                .map(|at| {
+                    let text = &doc.raw()[..at];
+                    let line = text.matches('\n').count() + 1;
+                    let col = text.rsplit('\n').next().unwrap().chars().count() + 1;
+                    format!(":{line}:{col}")
+                })
+                .unwrap_or_default();
+            let level = if problem.error { "error" } else { "warning" };
+            eprintln!("{}{at}: {level}: {}", path.display(), problem.message);
+            if let Some(help) = problem.help {
+                eprintln!("    help: {help}");
+            }
+        }
+    }
+    ok
+}
+
+fn check(settings: &toml::Table, styles: &[String]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    for (id, val) in settings {
+        let Val::Table(table) = val else {
+            problems.push(Problem::error(
+                &[id],
+                false,
+                format!("`{id}` must be a table"),
+            ));
+            continue;
+        };
+        match id.as_str() {
+            "page" | "page-number" => {
+                let keys = if id == "page" {
+                    PAGE_KEYS
+                } else {
+                    PAGE_NUMBER_KEYS
+                };
+                for (key, val) in table {
+                    if unknown(&mut problems, &[id, key], keys) {
+                        continue;
+                    }
+                    let single = single(&[id, key], val);
+                    report(&mut problems, &[id, key], section::check(&single));
+                }
+            }
+            "header" | "footer" => {
+                for (key, val) in table {
+                    if unknown(&mut problems, &[id, key], HEADER_FOOTER_KEYS) {
+                        continue;
+                    }
+                    if let ("first" | "even", Val::Table(sub)) = (key.as_str(), val) {
+                        for (sub_key, val) in sub {
+                            let path = [id, key, sub_key];
+                            if !unknown(&mut problems, &path, &["text", "align"]) {
+                                check_header_footer(&mut problems, &path, val);
+                            }
+                        }
+                    } else {
+                        check_header_footer(&mut problems, &[id, key], val);
+                    }
+                }
+            }
+            _ => check_style(&mut problems, settings, id, table, styles),
+        }
+    }
+    problems
+}
+
+fn check_header_footer(problems: &mut Vec<Problem>, path: &[&String], val: &Val) {
+    let single = single(path, val);
+    let id = path[0];
+    report(problems, path, header_footer::check(&single[id], id));
+}
+
+fn check_style(
+    problems: &mut Vec<Problem>,
+    settings: &toml::Table,
+    id: &String,
+    table: &toml::Table,
+    styles: &[String],
+) {
+    let builtin = is_builtin(id);
+    if !builtin && !table.contains_key("type") {
+        let candidates = (1..=9)
+            .map(|depth| format!("Heading{depth}"))
+            .chain(
+                BUILTIN_STYLE_IDS
+                    .iter()
+                    .chain(SETTING_TABLES)
+                    .map(|&id| id.into()),
+            )
+            .collect::<Vec<_>>();
+        if let Some(similar) = suggest(id, &candidates) {
+            problems.push(Problem {
+                path: vec![id.clone()],
+                on_key: true,
+                message: format!(
+                    "`{id}` is not a built-in style and will be added as a custom paragraph style"
+                ),
+                help: Some(format!(
+                    "did you mean `{similar}`? Add `type` to keep it as a custom style"
+                )),
+                error: false,
+            });
+        }
+    }
+
+    let style_type = match (id.as_str(), table.get("type")) {
+        (_, Some(Val::String(t))) if t == "character" => docx::StyleType::Character,
+        (_, Some(Val::String(t))) if t == "table" => docx::StyleType::Table,
+        ("InlineCode" | "CodeLineNumber", _) => docx::StyleType::Character,
+        ("Table", _) => docx::StyleType::Table,
+        _ => docx::StyleType::Paragraph,
+    };
+    for (key, val) in table {
+        let path = [id, key];
+        if unknown(problems, &path, STYLE_KEYS) {
+            continue;
+        }
+        match (key.as_str(), val) {
+            ("type", _) if builtin => {
+                problems.push(Problem::error(
+                    &path,
+                    true,
+                    format!("`type` can only be set on custom styles, `{id}` is built-in"),
+                ));
+                continue;
+            }
+            ("type", _) => {
+                report(problems, &path, style::custom_type(val).map(|_| ()));
+                continue;
+            }
+            ("based-on" | "next", Val::String(base)) => {
+                // 各语言的代码样式和嵌套引用的样式在用到时生成
+                let generated = base.starts_with("Code-")
+                    || base
+                        .strip_prefix("Quote")
+                        .is_some_and(|depth| depth.parse::<u8>().is_ok());
+                if !styles.contains(base) && !generated {
+                    problems.push(Problem {
+                        help: suggest(base, styles).map(|s| format!("did you mean `{s}`?")),
+                        ..Problem::error(&path, false, format!("Undefined style `{base}`"))
+                    });
+                }
+                if key == "based-on" {
+                    if let Some(cycle) = based_on_cycle(id, settings) {
+                        problems.push(Problem::error(
+                            &path,
+                            false,
+                            format!("based-on cycle: {cycle}"),
+                        ));
+                    }
+                }
+            }
+            (_, Val::Table(sub)) => {
+                if let Some(keys) = style::sub_keys(key) {
+                    for sub_key in sub.keys() {
+                        unknown(problems, &[id, key, sub_key], keys);
+                    }
+                }
+            }
+            _ => {}
+        }
+        report(problems, &path, style::check(id, style_type, key, val));
+    }
+}
+
+/// 沿 `based-on` 查找回到 `id` 自身的环，只在同一文件中查找
+fn based_on_cycle(id: &str, settings: &toml::Table) -> Option<String> {
+    let based_on = |id: &str| match settings.get(id) {
+        Some(Val::Table(style)) => match style.get("based-on") {
+            Some(Val::String(base)) => Some(base.as_str()),
+            _ => None,
+        },
+        _ => None,
+    };
+    let mut chain = vec![id];
+    while let Some(base) = based_on(chain.last().unwrap()) {
+        if base == id {
+            chain.push(base);
+            return Some(chain.join(" -> "));
+        }
+        if chain.contains(&base) {
+            return None;
+        }
+        chain.push(base);
+    }
+    None
+}
+
+/// 键不在 `keys` 中时记录错误并给出相近的键
+fn unknown(problems: &mut Vec<Problem>, path: &[&String], keys: &[&str]) -> bool {
+    let key = path.last().unwrap();
+    if keys.contains(&key.as_str()) {
+        return false;
+    }
+    let keys = keys.iter().map(|&k| k.into()).collect::<Vec<_>>();
+    let context = path[..path.len() - 1]
+        .iter()
+        .map(|s| s.as_str())
+        .collect::<Vec<_>>()
+        .join(".");
+    problems.push(Problem {
+        help: suggest(key, &keys).map(|s| format!("did you mean `{s}`?")),
+        ..Problem::error(
+            path,
+            true,
+            format!("Unknown setting `{key}` in [{context}]"),
+        )
+    });
+    true
+}
+
+/// 将设置的检查结果记录为错误
+fn report(problems: &mut Vec<Problem>, path: &[&String], result: Result<(), String>) {
+    if let Err(message) = result {
+        problems.push(Problem::error(path, false, message));
+    }
+}
+
+/// 只包含路径上一个值的设置表
+fn single(path: &[&String], val: &Val) -> toml::Table {
+    let (last, parents) = path.split_last().unwrap();
+    let mut table = toml::Table::from_iter([((*last).clone(), val.clone())]);
+    for key in parents.iter().rev() {
+        table = toml::Table::from_iter([((*key).clone(), Val::Table(table))]);
+    }
+    table
+}
+
+/// 编辑距离足够近的候选项
+fn suggest<'a>(word: &str, candidates: &'a [String]) -> Option<&'a str> {
+    let limit = (word.chars().count() / 3).max(1);
+    candidates
+        .iter()
+        .map(|c| {
+            let distance = strsim::damerau_levenshtein(&word.to_lowercase(), &c.to_lowercase());
+            (distance, c)
+        })
+        .filter(|&(d, _)| d <= limit)
+        .min_by_key(|&(d, _)| d)
+        .map(|(_, c)| c.as_str())
+}
+
+/// 键路径在文件中的位置，找不到时退回到最近的上层
+fn locate(doc: &ImDocument<String>, path: &[String], on_key: bool) -> Option<Range<usize>> {
+    let mut item = doc.as_item();
+    let mut found = None;
+    for (i, seg) in path.iter().enumerate() {
+        let (key, child) = item.as_table_like()?.get_key_value(seg)?;
+        let span = if on_key || i + 1 < path.len() {
+            key.span().or_else(|| child.span())
+        } else {
+            child.span().or_else(|| key.span())
+        };
+        found = span.or(found);
+        item = child;
+    }
+    found
+}
+
+impl Problem {
+    fn error(path: &[&String], on_key: bool, message: String) -> Self {
+        Self {
+            path: path.iter().map(|s| (*s).clone()).collect(),
+            on_key,
+            message,
+            help: None,
+            error: true,
+        }
+    }
+}
@@ -0,0 +1,716 @@
+//! 代码块信息串中的设置，从源文件读取代码，以及语法高亮
+//!
+//! ```` ```rust file=src/main.rs lines=10-40 ```` 读取文件的指定行，`anchor=name` 读取
+//! `ANCHOR: name` 和 `ANCHOR_END: name` 两行之间的代码，信息串中的其他文字原样作为题注。
+//! `linenos` 显示行号，`start=10` 设置起始行号，`hl=3-5,8` 突出显示指定的行。
+
+use super::{docx, style};
+use std::{
+    fs::read_to_string,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering::Relaxed},
+        LazyLock, Mutex,
+    },
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Color, FontStyle, Style, Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+use toml::Value as Val;
+
+/// 未设置 `syntax-theme` 时使用的高亮主题
+const DEFAULT_THEME: &str = "InspiredGitHub";
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+/// 代码高亮主题，`None` 表示不高亮
+static THEME: LazyLock<Mutex<Option<Theme>>> =
+    LazyLock::new(|| Mutex::new(Some(THEMES.themes[DEFAULT_THEME].clone())));
+/// 代码块是否排版为一组段落，而不是表格
+static PARAGRAPH_LAYOUT: AtomicBool = AtomicBool::new(false);
+/// 代码块是否保持在同一页
+static KEEP_TOGETHER: AtomicBool = AtomicBool::new(false);
+/// 代码块默认是否显示行号
+static LINE_NUMBERS: AtomicBool = AtomicBool::new(false);
+/// 默认的制表符宽度，保留制表符时也按此宽度估算折行位置
+const DEFAULT_TAB_WIDTH: usize = 4;
+/// 制表符展开的宽度，0 表示保留制表符
+static TAB_WIDTH: AtomicUsize = AtomicUsize::new(DEFAULT_TAB_WIDTH);
+/// 超过此宽度的行拆开并加折行标记，0 表示不拆分
+static WRAP_AT: AtomicUsize = AtomicUsize::new(0);
+/// 折行后续各段开头的标记
+static WRAP_MARKER: LazyLock<Mutex<String>> = LazyLock::new(|| Mutex::new("↪".into()));
+/// 突出显示的行的底色
+static LINE_HIGHLIGHT: LazyLock<Mutex<String>> = LazyLock::new(|| Mutex::new("FFF2CC".into()));
+
+/// 差异代码块中各行的类型
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Diff {
+    Insert,
+    Delete,
+    /// `@@` 开头的块头，以及其前的文件头
+    Hunk,
+    Context,
+}
+
+/// 代码块信息串中可以设置的键
+pub const CODE_KEYS: &[&str] = &["file", "lines", "anchor", "linenos", "start", "hl"];
+
+/// 代码块的语言、设置和题注
+pub struct Meta {
+    pub lang: String,
+    pub keys: Vec<(String, String)>,
+    pub caption: String,
+}
+
+impl Meta {
+    /// `lang` 是信息串的第一个词，也可能是设置
+    ///
+    /// 只有 `CODE_KEYS` 中的 `key=value` 和单独的 `linenos` 作为设置，其他文字原样作为题注。
+    /// 用引号括起的词总是题注的一部分，如 `"linenos"`。
+    pub fn parse(lang: Option<String>, meta: Option<String>) -> Self {
+        let info = [lang.as_deref(), meta.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut ans = Self {
+            lang: String::new(),
+            keys: Vec::new(),
+            caption: String::new(),
+        };
+        let mut end = 0;
+        for (i, range) in words(&info).into_iter().enumerate() {
+            let word = &info[range.clone()];
+            match word.split_once('=') {
+                Some((key, val)) if CODE_KEYS.contains(&key) => {
+                    ans.keys.push((key.into(), unquote(val).into()))
+                }
+                None if word == "linenos" => ans.keys.push((word.into(), "true".into())),
+                _ if i == 0 && lang.is_some() => ans.lang = word.into(),
+                _ => {
+                    // 保留题注中词之间原有的空白
+                    if !ans.caption.is_empty() {
+                        ans.caption.push_str(&info[end..range.start]);
+                    }
+                    ans.caption.push_str(unquote(word));
+                }
+            }
+            end = range.end;
+        }
+        ans
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.keys
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// 起始行号，不显示行号时为 `None`，设置了 `start` 时总是显示行号
+    pub fn start(&self) -> Option<usize> {
+        let linenos = match self.get("linenos") {
+            Some("true") => true,
+            Some("false") => false,
+            Some(val) => panic!("Invalid linenos: {val}, must be true or false"),
+            None => LINE_NUMBERS.load(Relaxed),
+        };
+        match self.get("start") {
+            Some(n) => Some(
+                n.parse()
+                    .unwrap_or_else(|_| panic!("Invalid start line number: {n}")),
+            ),
+            None => linenos.then_some(1),
+        }
+    }
+
+    /// 共 `count` 行代码中每行是否突出显示，`hl` 中的行号与显示的行号一致
+    pub fn highlighted(&self, count: usize) -> Vec<bool> {
+        let mut ans = vec![false; count];
+        if let Some(hl) = self.get("hl").filter(|_| count > 0) {
+            let first = self.start().unwrap_or(1);
+            for (start, end) in ranges(hl, first, first + count - 1) {
+                ans[start - first..=end - first].fill(true);
+            }
+        }
+        ans
+    }
+}
+
+/// 代码块的内容，设置了 `file` 时从文件读取，路径相对于 `dir`
+pub fn source(value: String, meta: &Meta, dir: &Path) -> String {
+    let Some(file) = meta.get("file") else {
+        return value;
+    };
+    let path = dir.join(file);
+    let text = read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Failed to read code file {}: {e}", path.display()));
+    let lines = text.lines().collect::<Vec<_>>();
+
+    let lines = match meta.get("anchor") {
+        Some(name) => anchor(&lines, name)
+            .unwrap_or_else(|| panic!("Anchor not found in {}: {name}", path.display())),
+        None => lines,
+    };
+    let lines = match meta.get("lines") {
+        Some(ranges) => select(&lines, ranges),
+        None => lines,
+    };
+    lines.join("\n")
+}
+
+/// 将信息串中 `file=` 的路径改为相对于 `dir`，用于包含其他文件中的代码块
+pub fn rebase(meta: &str, dir: &Path) -> String {
+    let mut ans = meta.to_string();
+    for range in words(meta).into_iter().rev() {
+        if let Some(file) = meta[range.clone()].strip_prefix("file=") {
+            let path = dir.join(unquote(file)).display().to_string();
+            let path = match path.contains(char::is_whitespace) {
+                true => format!(r#""{path}""#),
+                false => path,
+            };
+            ans.replace_range(range, &format!("file={path}"));
+        }
+    }
+    ans
+}
+
+/// 信息串中各词的范围，以空白分隔，引号中的空白不分隔
+fn words(info: &str) -> Vec<std::ops::Range<usize>> {
+    let mut ans = Vec::new();
+    let mut start = None;
+    let mut quoted = false;
+    for (i, c) in info.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if let Some(start) = start.take() {
+                    ans.push(start..i);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(start) = start {
+        ans.push(start..info.len());
+    }
+    ans
+}
+
+/// 去掉两端的引号
+fn unquote(text: &str) -> &str {
+    text.strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .unwrap_or(text)
+}
+
+/// mdBook 格式的锚点之间的行，去掉其中其他锚点的标记行
+fn anchor<'a>(lines: &[&'a str], name: &str) -> Option<Vec<&'a str>> {
+    let is_marker = |line: &str, tag: &str| {
+        line.split_once(tag).is_some_and(|(_, rest)| {
+            rest.trim_start()
+                .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                .next()
+                == Some(name)
+        })
+    };
+    let start = lines.iter().position(|l| is_marker(l, "ANCHOR:"))?;
+    let len = lines[start + 1..]
+        .iter()
+        .position(|l| is_marker(l, "ANCHOR_END:"))?;
+    Some(
+        lines[start + 1..start + 1 + len]
+            .iter()
+            .filter(|l| !l.contains("ANCHOR:") && !l.contains("ANCHOR_END:"))
+            .copied()
+            .collect(),
+    )
+}
+
+/// 按 `10-40`、`5`、`-3`、`20-` 形式的行号范围选出行，多个范围以逗号分隔，行号从 1 开始
+fn select<'a>(lines: &[&'a str], text: &str) -> Vec<&'a str> {
+    let mut ans = Vec::new();
+    for (start, end) in ranges(text, 1, lines.len()) {
+        ans.extend_from_slice(&lines[start - 1..end]);
+    }
+    ans
+}
+
+/// 解析行号范围，省略的起止行号为 `first` 和 `last`，范围必须在两者之间
+fn ranges(text: &str, first: usize, last: usize) -> Vec<(usize, usize)> {
+    let mut ans = Vec::new();
+    for range in text.split(',') {
+        let num = |s: &str, default: usize| -> usize {
+            if s.trim().is_empty() {
+                return default;
+            }
+            s.trim()
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid line range: {text}"))
+        };
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (num(start, first), num(end, last)),
+            None if range.trim().is_empty() => panic!("Invalid line range: {text}"),
+            None => (num(range, first), num(range, first)),
+        };
+        if start < first || start > end || end > last {
+            panic!("Line range {range} out of bounds, lines are numbered {first}-{last}");
+        }
+        ans.push((start, end));
+    }
+    ans
+}
+
+/// 读取样式文件中 `[Code]` 的排版、高亮主题、行号、制表符和折行设置
+pub fn init(settings: &toml::Table) {
+    let Some(Val::Table(code)) = settings.get("Code") else {
+        return;
+    };
+    let get = |key: &'static str| code.get(key).map(|val| (val, key));
+    fn valid<T>(result: Result<T, String>) -> T {
+        result.unwrap_or_else(|e| panic!("Invalid style Code: {e}"))
+    }
+    if let Some((val, _)) = get("layout") {
+        PARAGRAPH_LAYOUT.store(valid(style::layout(val)), Relaxed);
+    }
+    if let Some((val, key)) = get("keep-together") {
+        KEEP_TOGETHER.store(valid(flag(val, key)), Relaxed);
+    }
+    if let Some((val, _)) = get("syntax-theme") {
+        *THEME.lock().unwrap() = valid(theme(val));
+    }
+    if let Some((val, key)) = get("line-numbers") {
+        LINE_NUMBERS.store(valid(flag(val, key)), Relaxed);
+    }
+    if let Some((val, key)) = get("line-highlight") {
+        *LINE_HIGHLIGHT.lock().unwrap() = valid(style::color(val, key));
+    }
+    if let Some((val, key)) = get("tab-width") {
+        TAB_WIDTH.store(valid(columns(val, key)), Relaxed);
+    }
+    if let Some((val, key)) = get("wrap-at") {
+        WRAP_AT.store(valid(columns(val, key)), Relaxed);
+    }
+    if let Some((val, _)) = get("wrap-marker") {
+        *WRAP_MARKER.lock().unwrap() = valid(wrap_marker(val));
+    }
+}
+
+/// 以字符数表示的宽度，0 表示关闭
+pub fn columns(val: &Val, key: &str) -> Result<usize, String> {
+    match *val {
+        Val::Integer(n) if n >= 0 => Ok(n as usize),
+        _ => Err(format!("{key} must be a non-negative integer")),
+    }
+}
+
+pub fn wrap_marker(val: &Val) -> Result<String, String> {
+    match val {
+        Val::String(marker) => Ok(marker.clone()),
+        _ => Err("wrap-marker must be a string".into()),
+    }
+}
+
+pub fn flag(val: &Val, key: &str) -> Result<bool, String> {
+    match *val {
+        Val::Boolean(on) => Ok(on),
+        _ => Err(format!("{key} must be a boolean")),
+    }
+}
+
+pub fn paragraph_layout() -> bool {
+    PARAGRAPH_LAYOUT.load(Relaxed)
+}
+
+pub fn keep_together() -> bool {
+    KEEP_TOGETHER.load(Relaxed)
+}
+
+/// 突出显示的行的底色
+pub fn line_highlight() -> String {
+    LINE_HIGHLIGHT.lock().unwrap().clone()
+}
+
+/// 内置主题名或 `.tmTheme` 文件的路径，`none` 表示不高亮
+pub fn theme(val: &Val) -> Result<Option<Theme>, String> {
+    let Val::String(name) = val else {
+        return Err("syntax-theme must be a string".into());
+    };
+    if name == "none" {
+        return Ok(None);
+    }
+    if let Some(theme) = THEMES.themes.get(name) {
+        return Ok(Some(theme.clone()));
+    }
+    match ThemeSet::get_theme(name) {
+        Ok(theme) => Ok(Some(theme)),
+        Err(_) => Err(format!(
+            "Unknown syntax theme: {name}, must be none, a .tmTheme file or one of {}",
+            THEMES.themes.keys().cloned().collect::<Vec<_>>().join(", ")
+        )),
+    }
+}
+
+/// 将代码转换为段落，每行一段，按设置展开制表符、高亮并为过长的行加折行标记
+pub fn paragraphs(code: &str, lang: &str) -> Vec<docx::Paragraph> {
+    let tab_width = TAB_WIDTH.load(Relaxed);
+    let code = if tab_width > 0 {
+        code.lines()
+            .map(|line| expand_tabs(line, tab_width))
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        code.into()
+    };
+    let lines = highlight(&code, lang).unwrap_or_else(|| {
+        code.lines()
+            .map(|line| vec![(None, line.to_string())])
+            .collect()
+    });
+    let wrap_at = WRAP_AT.load(Relaxed);
+    lines
+        .into_iter()
+        .map(|tokens| {
+            let mut p = docx::Paragraph::new();
+            for (i, segment) in wrap(tokens, wrap_at).into_iter().enumerate() {
+                if i > 0 {
+                    p = p.add_run(
+                        docx::Run::new()
+                            .add_break(docx::BreakType::TextWrapping)
+                            .add_text(format!("{} ", WRAP_MARKER.lock().unwrap()))
+                            .color("808080"),
+                    );
+                }
+                for (style, text) in segment {
+                    p = p.add_run(run(style, &text));
+                }
+            }
+            p
+        })
+        .collect()
+}
+
+/// 是否是按差异着色的语言
+pub fn is_diff(lang: &str) -> bool {
+    lang.eq_ignore_ascii_case("diff") || lang.eq_ignore_ascii_case("patch")
+}
+
+/// 差异代码块中各行的类型，块头之前的 `diff`、`index`、`---`、`+++` 等行是文件头
+pub fn diff_lines(code: &str) -> Vec<Diff> {
+    const HEADERS: &[&str] = &[
+        "diff ",
+        "index ",
+        "--- ",
+        "+++ ",
+        "new file",
+        "deleted file",
+    ];
+    let mut header = true;
+    code.lines()
+        .map(|line| {
+            if line.starts_with("diff ") {
+                header = true;
+            }
+            if line.starts_with("@@") {
+                header = false;
+                return Diff::Hunk;
+            }
+            match line.chars().next() {
+                _ if header && HEADERS.iter().any(|h| line.starts_with(h)) => Diff::Hunk,
+                Some('+') => Diff::Insert,
+                Some('-') => Diff::Delete,
+                _ => Diff::Context,
+            }
+        })
+        .collect()
+}
+
+/// 一段文字及其高亮样式
+type Token = (Option<Style>, String);
+
+/// 按语言高亮代码，每行是一组带样式的文字，不高亮或不认识的语言返回 `None`
+///
+/// 差异代码块由各行的样式着色，不再高亮。
+fn highlight(code: &str, lang: &str) -> Option<Vec<Vec<Token>>> {
+    if is_diff(lang) {
+        return None;
+    }
+    let theme = THEME.lock().unwrap();
+    let theme = theme.as_ref()?;
+    let syntax = SYNTAXES.find_syntax_by_token(lang)?;
+    let mut highlighter = HighlightLines::new(syntax, theme);
+
+    let mut ans = Vec::new();
+    for line in LinesWithEndings::from(code) {
+        let tokens = highlighter.highlight_line(line, &SYNTAXES).ok()?;
+        let default = theme.settings.foreground;
+        let tokens = tokens
+            .into_iter()
+            .map(|(mut style, text)| {
+                // 与主题默认前景色相同的文字不设置颜色，沿用代码样式的颜色
+                if Some(style.foreground) == default {
+                    style.foreground.a = 0;
+                }
+                (Some(style), text.trim_end_matches(['\n', '\r']).to_string())
+            })
+            .filter(|(_, text)| !text.is_empty())
+            .collect();
+        ans.push(tokens);
+    }
+    Some(ans)
+}
+
+/// 带高亮样式的文字，不展开制表符时制表符输出为 `w:tab`
+fn run(style: Option<Style>, text: &str) -> docx::Run {
+    let mut run = docx::Run::new();
+    for (i, part) in text.split('\t').enumerate() {
+        if i > 0 {
+            run = run.add_tab();
+        }
+        if !part.is_empty() {
+            run = run.add_text(part);
+        }
+    }
+    let Some(style) = style else {
+        return run;
+    };
+    if style.foreground.a != 0 {
+        run = run.color(hex(style.foreground));
+    }
+    if style.font_style.contains(FontStyle::BOLD) {
+        run = run.bold();
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        run = run.italic();
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        run = run.underline("single");
+    }
+    run
+}
+
+/// 将制表符展开为空格，对齐到 `width` 的整数倍列
+fn expand_tabs(line: &str, width: usize) -> String {
+    let mut ans = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let n = width - column % width;
+            ans.extend(std::iter::repeat_n(' ', n));
+            column += n;
+        } else {
+            ans.push(c);
+            column += char_width(c);
+        }
+    }
+    ans
+}
+
+/// 将一行按宽度拆成多段，`width` 为 0 时不拆分，后续各段的宽度扣除折行标记的宽度
+///
+/// 展开制表符时这里已没有制表符；保留的制表符按制表位对齐计算宽度。
+fn wrap(tokens: Vec<Token>, width: usize) -> Vec<Vec<Token>> {
+    if width == 0 {
+        return vec![tokens];
+    }
+    let marker = WRAP_MARKER
+        .lock()
+        .unwrap()
+        .chars()
+        .map(char_width)
+        .sum::<usize>()
+        + 1;
+    let tab_width = match TAB_WIDTH.load(Relaxed) {
+        0 => DEFAULT_TAB_WIDTH,
+        n => n,
+    };
+    // 制表位从行首算起，后续各段开头有折行标记
+    let (mut offset, mut column, mut limit) = (0, 0, width);
+    let width_of = |c: char, column: usize| match c {
+        '\t' => tab_width - column % tab_width,
+        _ => char_width(c),
+    };
+    let mut ans = vec![Vec::new()];
+    for (style, text) in tokens {
+        let mut current = String::new();
+        for c in text.chars() {
+            let mut w = width_of(c, offset + column);
+            if column + w > limit && column > 0 {
+                if !current.is_empty() {
+                    ans.last_mut()
+                        .unwrap()
+                        .push((style, std::mem::take(&mut current)));
+                }
+                ans.push(Vec::new());
+                (offset, column) = (marker, 0);
+                limit = width.saturating_sub(marker).max(1);
+                w = width_of(c, offset);
+            }
+            current.push(c);
+            column += w;
+        }
+        if !current.is_empty() {
+            ans.last_mut().unwrap().push((style, current));
+        }
+    }
+    ans
+}
+
+/// 等宽字体中字符所占的列数，中日韩文字和全角字符占两列
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+fn hex(color: Color) -> String {
+    format!("{:02X}{:02X}{:02X}", color.r, color.g, color.b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(lang: &str, meta: &str) -> Meta {
+        let some = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        Meta::parse(some(lang), some(meta))
+    }
+
+    #[test]
+    fn parses_lang_settings_and_caption() {
+        let meta = parse("rust", r#"file="a b.rs" linenos hl=2  程序  入口"#);
+        assert_eq!(meta.lang, "rust");
+        assert_eq!(meta.get("file"), Some("a b.rs"));
+        assert_eq!(meta.get("linenos"), Some("true"));
+        assert_eq!(meta.get("hl"), Some("2"));
+        assert_eq!(meta.caption, "程序  入口");
+    }
+
+    #[test]
+    fn keeps_unknown_and_quoted_words_in_caption() {
+        let meta = parse("rust", r#"a=b "linenos" 说明"#);
+        assert!(meta.keys.is_empty());
+        assert_eq!(meta.caption, "a=b linenos 说明");
+    }
+
+    #[test]
+    fn parses_settings_without_lang() {
+        let meta = parse("linenos", "start=3");
+        assert_eq!(meta.lang, "");
+        assert_eq!(meta.get("start"), Some("3"));
+        assert_eq!(meta.start(), Some(3));
+    }
+
+    #[test]
+    fn splits_words_outside_quotes() {
+        let info = r#" a "b c"  d"#;
+        let words = words(info)
+            .into_iter()
+            .map(|r| &info[r])
+            .collect::<Vec<_>>();
+        assert_eq!(words, ["a", r#""b c""#, "d"]);
+    }
+
+    #[test]
+    fn rebases_only_file_paths() {
+        let dir = Path::new("sub dir");
+        assert_eq!(
+            rebase("rust file=a.rs  说明 lines=1-2", dir),
+            format!(
+                r#"rust file="{}"  说明 lines=1-2"#,
+                dir.join("a.rs").display()
+            )
+        );
+        assert_eq!(rebase("file 说明", dir), "file 说明");
+    }
+
+    #[test]
+    fn parses_line_ranges() {
+        assert_eq!(
+            ranges("2, 4-5,-3,9-", 1, 10),
+            [(2, 2), (4, 5), (1, 3), (9, 10)]
+        );
+        assert_eq!(ranges("12-", 10, 13), [(12, 13)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds, lines are numbered 10-13")]
+    fn rejects_ranges_before_first_line() {
+        ranges("9-11", 10, 13);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid line range")]
+    fn rejects_empty_ranges() {
+        ranges("1,,2", 1, 10);
+    }
+
+    #[test]
+    fn highlights_displayed_line_numbers() {
+        let meta = parse("rust", "start=10 hl=11,13-");
+        assert_eq!(meta.highlighted(4), [false, true, false, true]);
+        assert!(meta.highlighted(0).is_empty());
+    }
+
+    /// 折行后各段的文字
+    fn wrapped(tokens: &[&str], width: usize) -> Vec<String> {
+        let tokens = tokens.iter().map(|&t| (None, t.to_string())).collect();
+        wrap(tokens, width)
+            .into_iter()
+            .map(|line| line.into_iter().map(|(_, text)| text).collect())
+            .collect()
+    }
+
+    #[test]
+    fn wraps_by_display_width() {
+        assert_eq!(wrapped(&["abcdef"], 0), ["abcdef"]);
+        // 后续各段扣除折行标记和空格的宽度
+        assert_eq!(wrapped(&["abc", "defgh"], 4), ["abcd", "ef", "gh"]);
+        assert_eq!(wrapped(&["中文字"], 4), ["中文", "字"]);
+        assert_eq!(wrapped(&["a中"], 2), ["a", "中"]);
+    }
+
+    #[test]
+    fn measures_kept_tabs_by_tab_stop() {
+        assert_eq!(wrapped(&["a\tb"], 5), ["a\tb"]);
+        assert_eq!(wrapped(&["a\tb"], 4), ["a\t", "b"]);
+        // 后续各段的制表位从行首算起，包括折行标记
+        assert_eq!(wrapped(&["abcdef\txy"], 6), ["abcdef", "\txy"]);
+    }
+
+    #[test]
+    fn expands_tabs_to_tab_stops() {
+        assert_eq!(expand_tabs("a\tb", 4), "a   b");
+        assert_eq!(expand_tabs("\tb", 4), "    b");
+        assert_eq!(expand_tabs("中\tb\t", 4), "中  b   ");
+    }
+
+    #[test]
+    fn classifies_diff_lines() {
+        use Diff::{Context, Delete, Hunk, Insert};
+        let code = "diff --git a/x b/x\nindex 1..2\n--- a/x\n+++ b/x\n@@ -1,2 +1,2 @@\n-old\n+new\n same\n--- removed dashes\n+++ added pluses";
+        assert_eq!(
+            diff_lines(code),
+            [Hunk, Hunk, Hunk, Hunk, Hunk, Delete, Insert, Context, Delete, Insert]
+        );
+        assert_eq!(diff_lines("+a\n-b\nc"), [Insert, Delete, Context]);
+    }
+}
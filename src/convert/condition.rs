@@ -0,0 +1,215 @@
+//! 按 `--profile` 保留或删除的条件块
+//!
+//! 支持 `<!-- if name -->`、`<!-- else -->`、`<!-- endif -->` 注释指令和 `::: {.only profile=name}` 块指令。
+
+use super::{
+    directive::{self, Fence, ONLY},
+    Ast,
+};
+use std::ops::Range;
+
+/// 条件：任一项成立时保留，`!name` 表示未启用 `name` 时成立
+struct Condition(Vec<(bool, String)>);
+
+/// 正在处理的条件块或普通块指令
+enum Frame {
+    /// 注释指令，记录是否保留以及是否已进入 `else`
+    If { keep: bool, otherwise: bool },
+    /// 块指令，`keep` 为 `None` 表示不是条件块
+    Fence { keep: Option<bool> },
+}
+
+/// 删除未启用的条件块以及条件指令本身
+pub fn filter(children: Vec<Ast>, profiles: &[String]) -> Vec<Ast> {
+    let keep = keep(&children, profiles);
+    children
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(ast, keep)| keep.then_some(ast))
+        .collect()
+}
+
+/// 被删除的节点在源文本中的范围
+pub fn dropped(children: &[Ast], profiles: &[String]) -> Vec<Range<usize>> {
+    children
+        .iter()
+        .zip(keep(children, profiles))
+        .filter(|(_, keep)| !keep)
+        .filter_map(|(ast, _)| ast.position())
+        .map(|p| p.start.offset..p.end.offset)
+        .collect()
+}
+
+/// 文档中条件指令提到的所有 profile
+pub fn profiles(children: &[Ast]) -> Vec<String> {
+    let mut ans = Vec::new();
+    for ast in children {
+        let condition = match (directive::comment(ast), directive::fence(ast)) {
+            (Some(comment), _) => comment.strip_prefix("if ").map(Condition::parse),
+            (_, Some(Fence::Open(attrs))) if attrs.has(ONLY) => {
+                attrs.get("profile").map(Condition::parse)
+            }
+            _ => None,
+        };
+        for (_, name) in condition.into_iter().flat_map(|c| c.0) {
+            if !ans.contains(&name) {
+                ans.push(name);
+            }
+        }
+    }
+    ans
+}
+
+/// 每个节点是否保留
+fn keep(children: &[Ast], profiles: &[String]) -> Vec<bool> {
+    let mut frames = Vec::<Frame>::new();
+    let visible = |frames: &[Frame]| {
+        frames.iter().all(|f| match f {
+            Frame::If { keep, .. } => *keep,
+            Frame::Fence { keep } => keep.unwrap_or(true),
+        })
+    };
+    let mut ans = Vec::with_capacity(children.len());
+    for ast in children {
+        if let Some(comment) = directive::comment(ast) {
+            let word = comment.split_whitespace().next().unwrap_or_default();
+            match word {
+                "if" => frames.push(Frame::If {
+                    keep: Condition::parse(&comment[2..]).matches(profiles),
+                    otherwise: false,
+                }),
+                "else" => match frames.last_mut() {
+                    Some(Frame::If { keep, otherwise }) if !*otherwise => {
+                        *keep = !*keep;
+                        *otherwise = true;
+                    }
+                    _ => panic!("Unexpected <!-- else -->"),
+                },
+                "endif" => match frames.pop() {
+                    Some(Frame::If { .. }) => {}
+                    _ => panic!("Unexpected <!-- endif -->"),
+                },
+                _ => {
+                    ans.push(visible(&frames));
+                    continue;
+                }
+            }
+            ans.push(false);
+            continue;
+        }
+        match directive::fence(ast) {
+            Some(Fence::Open(attrs)) => {
+                let keep = attrs.has(ONLY).then(|| {
+                    let profile = attrs
+                        .get("profile")
+                        .unwrap_or_else(|| panic!("::: only requires a profile"));
+                    Condition::parse(profile).matches(profiles)
+                });
+                ans.push(keep.is_none() && visible(&frames));
+                frames.push(Frame::Fence { keep });
+            }
+            Some(Fence::Close) => match frames.pop() {
+                Some(Frame::Fence { keep }) => ans.push(keep.is_none() && visible(&frames)),
+                Some(Frame::If { .. }) => panic!("Unclosed <!-- if --> before :::"),
+                // 多余的 `:::` 由转换代码处理
+                None => ans.push(true),
+            },
+            None => ans.push(visible(&frames)),
+        }
+    }
+    if frames.iter().any(|f| matches!(f, Frame::If { .. })) {
+        panic!("Unclosed <!-- if -->");
+    }
+    ans
+}
+
+impl Condition {
+    /// 解析以空格或逗号分隔的 profile 列表，`!name` 或 `not name` 表示取反
+    fn parse(text: &str) -> Self {
+        let mut items = Vec::new();
+        let mut negate = false;
+        for word in text.split([' ', ',', '|']).filter(|w| !w.is_empty()) {
+            match word {
+                "not" => negate = true,
+                word => {
+                    let (neg, name) = match word.strip_prefix('!') {
+                        Some(name) => (true, name),
+                        None => (false, word),
+                    };
+                    items.push((neg != negate, name.to_string()));
+                    negate = false;
+                }
+            }
+        }
+        if items.is_empty() {
+            panic!("Empty condition: {text}");
+        }
+        Self(items)
+    }
+
+    fn matches(&self, profiles: &[String]) -> bool {
+        self.0
+            .iter()
+            .any(|(neg, name)| profiles.contains(name) != *neg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::parse;
+
+    fn profiles(names: &[&str]) -> Vec<String> {
+        names.iter().map(|&name| name.into()).collect()
+    }
+
+    /// 保留的段落文字
+    fn kept(md: &str, enabled: &[&str]) -> Vec<String> {
+        filter(parse(md).children, &profiles(enabled))
+            .iter()
+            .map(Ast::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn keeps_if_or_else_branch() {
+        let md = "a\n\n<!-- if pdf -->\n\nb\n\n<!-- else -->\n\nc\n\n<!-- endif -->\n\nd";
+        assert_eq!(kept(md, &["pdf"]), ["a", "b", "d"]);
+        assert_eq!(kept(md, &[]), ["a", "c", "d"]);
+    }
+
+    #[test]
+    fn matches_any_item_and_negation() {
+        let md = "<!-- if web, !draft -->\n\nx\n\n<!-- endif -->\n\n<!-- if not web -->\n\ny\n\n<!-- endif -->";
+        assert_eq!(kept(md, &["web", "draft"]), ["x"]);
+        assert_eq!(kept(md, &["draft"]), ["y"]);
+        assert_eq!(kept(md, &[]), ["x", "y"]);
+    }
+
+    #[test]
+    fn keeps_nested_fences_and_drops_only_blocks() {
+        let md = "::: {.only profile=web}\n\n::: note\n\nx\n\n:::\n\n:::\n\ny";
+        assert_eq!(kept(md, &["web"]), ["::: note", "x", ":::", "y"]);
+        assert_eq!(kept(md, &[]), ["y"]);
+    }
+
+    #[test]
+    fn reports_dropped_ranges() {
+        let md = "a\n\n<!-- if pdf -->\n\nb\n\n<!-- endif -->\n";
+        let dropped = dropped(&parse(md).children, &[]);
+        let texts = dropped.into_iter().map(|r| &md[r]).collect::<Vec<_>>();
+        assert_eq!(texts, ["<!-- if pdf -->", "b", "<!-- endif -->"]);
+    }
+
+    #[test]
+    fn lists_profiles() {
+        let md = "<!-- if a | !b -->\n\nx\n\n<!-- endif -->\n\n::: {.only profile=c}\n\ny\n\n:::";
+        assert_eq!(super::profiles(&parse(md).children), ["a", "b", "c"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unclosed <!-- if -->")]
+    fn rejects_unclosed_if() {
+        kept("<!-- if pdf -->\n\nx", &[]);
+    }
+}
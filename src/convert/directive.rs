@@ -0,0 +1,188 @@
+use super::{md, Ast};
+use markdown::unist::{Point, Position};
+use std::ops::Range;
+
+/// 标记正文开始的注释指令
+pub const MAIN_MATTER: &str = "mainmatter";
+/// 横向页面的块指令
+pub const LANDSCAPE: &str = "landscape";
+/// 分栏的块指令
+pub const COLUMNS: &str = "columns";
+/// 包含其他文件的指令
+pub const INCLUDE: &str = "include";
+/// 只在指定 profile 中保留的块指令
+pub const ONLY: &str = "only";
+
+/// 解析 `<!-- name -->` 形式的注释指令，返回注释内容
+pub fn comment(ast: &Ast) -> Option<&str> {
+    let Ast::Html(md::Html { value, .. }) = ast else {
+        return None;
+    };
+    value
+        .trim()
+        .strip_prefix("<!--")
+        .and_then(|s| s.strip_suffix("-->"))
+        .map(str::trim)
+}
+
+/// `:::` 围起的块指令
+pub enum Fence {
+    Open(Attributes),
+    Close,
+}
+
+/// 块指令的属性，`::: name key=val` 和 `::: {.name key=val}` 两种写法等价
+#[derive(Default)]
+pub struct Attributes {
+    pub classes: Vec<String>,
+    pub keys: Vec<(String, String)>,
+}
+
+impl Attributes {
+    pub fn has(&self, class: &str) -> bool {
+        self.classes.iter().any(|c| c == class)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.keys
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// 解析单独成段的 `:::` 块指令
+pub fn fence(ast: &Ast) -> Option<Fence> {
+    let Ast::Paragraph(md::Paragraph { children, .. }) = ast else {
+        return None;
+    };
+    let [Ast::Text(md::Text { value, .. })] = &children[..] else {
+        return None;
+    };
+    // 多行的段落已在解析时拆开，`:::` 只能单独一行
+    if value.trim().contains('\n') {
+        return None;
+    }
+    let rest = value.trim().strip_prefix(":::")?;
+    let rest = rest.trim_matches(':').trim();
+    if rest.is_empty() {
+        return Some(Fence::Close);
+    }
+    let rest = rest
+        .strip_prefix('{')
+        .and_then(|s| s.strip_suffix('}'))
+        .unwrap_or(rest);
+
+    let mut attrs = Attributes::default();
+    for token in rest.split_whitespace() {
+        match token.split_once('=') {
+            Some((k, v)) => attrs.keys.push((k.into(), v.trim_matches('"').into())),
+            None => attrs
+                .classes
+                .push(token.strip_prefix('.').unwrap_or(token).into()),
+        }
+    }
+    Some(Fence::Open(attrs))
+}
+
+/// 多行段落中以 `:::` 开头的行，如紧接在 `::: landscape` 之后、没有空行分隔的内容
+///
+/// 只查找顶层的段落，返回各行去掉两端空白后在 `md` 中的范围。
+pub fn fence_lines(md: &str, children: &[Ast]) -> Vec<Range<usize>> {
+    let mut ans = Vec::new();
+    for ast in children {
+        let (Ast::Paragraph(_), Some(position)) = (ast, ast.position()) else {
+            continue;
+        };
+        let (start, end) = (position.start.offset, position.end.offset);
+        if !md[start..end].contains('\n') {
+            continue;
+        }
+        let mut at = start;
+        for line in md[start..end].split('\n') {
+            let trimmed = line.trim();
+            if trimmed.starts_with(":::") {
+                let offset = at + line.find(trimmed).unwrap();
+                ans.push(offset..offset + trimmed.len());
+            }
+            at += line.len() + 1;
+        }
+    }
+    ans
+}
+
+/// 将 `fence_lines` 找到的各行作为单独的段落，按位置插入到 `children` 中
+pub fn insert_fences(children: &mut Vec<Ast>, md: &str, lines: Vec<Range<usize>>) {
+    let point = |offset: usize| {
+        let before = &md[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Point::new(
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+            offset,
+        )
+    };
+    for range in lines {
+        let (start, end) = (point(range.start), point(range.end));
+        let position = Position { start, end };
+        let text = Ast::Text(md::Text {
+            value: md[range.clone()].into(),
+            position: Some(position.clone()),
+        });
+        let paragraph = Ast::Paragraph(md::Paragraph {
+            children: vec![text],
+            position: Some(position),
+        });
+        let at = children
+            .iter()
+            .position(|ast| ast.position().is_some_and(|p| p.start.offset > range.start))
+            .unwrap_or(children.len());
+        children.insert(at, paragraph);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::parse;
+
+    #[test]
+    fn splits_fences_from_adjacent_lines() {
+        let root = parse("::: landscape\nImportant *text*\n:::\n\nafter\n");
+        let texts = root.children.iter().map(Ast::to_string).collect::<Vec<_>>();
+        assert_eq!(texts, ["::: landscape", "Important text", ":::", "after"]);
+        let lines = root
+            .children
+            .iter()
+            .map(|ast| ast.position().unwrap().start.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [1, 2, 3, 5]);
+
+        let Some(Fence::Open(attrs)) = fence(&root.children[0]) else {
+            panic!("expected an opening fence");
+        };
+        assert!(attrs.has(LANDSCAPE));
+        assert!(matches!(fence(&root.children[2]), Some(Fence::Close)));
+        assert!(fence(&root.children[1]).is_none());
+    }
+
+    #[test]
+    fn parses_fence_attributes() {
+        let root = parse("::: {.only .wide profile=\"a\"} :::\n");
+        let Some(Fence::Open(attrs)) = fence(&root.children[0]) else {
+            panic!("expected an opening fence");
+        };
+        assert_eq!(attrs.classes, ["only", "wide"]);
+        assert_eq!(attrs.get("profile"), Some("a"));
+    }
+
+    #[test]
+    fn keeps_multi_line_fences_in_nested_paragraphs() {
+        let root = parse("> ::: note\n> text\n");
+        let Ast::BlockQuote(quote) = &root.children[0] else {
+            panic!("expected a block quote");
+        };
+        assert!(fence(&quote.children[0]).is_none());
+        assert_eq!(quote.children[0].to_string(), "::: note\ntext");
+    }
+}
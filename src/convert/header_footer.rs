@@ -0,0 +1,240 @@
+use super::{
+    docx::{self, Docx, Run},
+    metadata::title,
+    style::{footer_style, header_style, HEADING_STYLE_NAME},
+    variable::expand_text,
+};
+use toml::Value as Val;
+
+/// `[header]` 和 `[footer]` 中的设置，`first` 和 `even` 子表中只能设置 `text` 和 `align`
+pub const HEADER_FOOTER_KEYS: &[&str] = &["text", "align", "first", "even"];
+
+/// 按样式文件中的 `[header]` 和 `[footer]` 设置添加页眉页脚
+///
+/// 首页或奇偶页不同是整节的设置，因此只要页眉页脚之一设置了变体，另一个也使用其默认内容补全变体。
+pub fn add_header_footer(mut docx: Docx, settings: &toml::Table, metadata: &toml::Table) -> Docx {
+    let title = title(metadata);
+    let parse = |name: &str| {
+        settings.get(name).map(|val| {
+            if let Val::Table(table) = val {
+                for key in table.keys() {
+                    if !HEADER_FOOTER_KEYS.contains(&key.as_str()) {
+                        eprintln!("Unknown {name} setting: {key}");
+                    }
+                }
+            }
+            let variants = variants(val, name).unwrap_or_else(|e| panic!("{e}"));
+            let paragraph = |c: Content| c.paragraph(title);
+            (
+                paragraph(variants.0),
+                variants.1.map(paragraph),
+                variants.2.map(paragraph),
+            )
+        })
+    };
+    let header = parse("header");
+    let footer = parse("footer");
+    let has = |pick: fn(&Variants) -> bool| header.iter().chain(&footer).any(pick);
+    let first = has(|(_, first, _)| first.is_some());
+    let even = has(|(_, _, even)| even.is_some());
+
+    // docx-rs 按调用顺序分配关系 ID，但按默认、首页、偶数页的顺序编号部件，须先设置默认页眉页脚
+    if let Some((default, first_page, even_page)) = header {
+        let page = |p: docx::Paragraph| docx::Header::new().add_paragraph(header_style(p));
+        let (first_page, even_page) = (
+            first.then(|| first_page.unwrap_or_else(|| default.clone())),
+            even.then(|| even_page.unwrap_or_else(|| default.clone())),
+        );
+        docx = docx.header(page(default));
+        if let Some(p) = first_page {
+            docx = docx.first_header(page(p));
+        }
+        if let Some(p) = even_page {
+            docx = docx.even_header(page(p));
+        }
+    }
+    if let Some((default, first_page, even_page)) = footer {
+        let page = |p: docx::Paragraph| docx::Footer::new().add_paragraph(footer_style(p));
+        let (first_page, even_page) = (
+            first.then(|| first_page.unwrap_or_else(|| default.clone())),
+            even.then(|| even_page.unwrap_or_else(|| default.clone())),
+        );
+        docx = docx.footer(page(default));
+        if let Some(p) = first_page {
+            docx = docx.first_footer(page(p));
+        }
+        if let Some(p) = even_page {
+            docx = docx.even_footer(page(p));
+        }
+    }
+    docx
+}
+
+/// 检查 `[header]` 或 `[footer]` 的设置，`name` 为表名
+pub fn check(val: &Val, name: &str) -> Result<(), String> {
+    variants(val, name).map(|_| ())
+}
+
+type Variants = (
+    docx::Paragraph,
+    Option<docx::Paragraph>,
+    Option<docx::Paragraph>,
+);
+
+/// 一种变体的文字和对齐方式
+struct Content<'a> {
+    text: Option<&'a str>,
+    align: Option<docx::AlignmentType>,
+}
+
+/// 解析默认、首页和偶数页三种变体，`first` 和 `even` 继承默认设置
+fn variants<'a>(
+    val: &'a Val,
+    name: &str,
+) -> Result<(Content<'a>, Option<Content<'a>>, Option<Content<'a>>), String> {
+    let Val::Table(settings) = val else {
+        return Err(format!("Invalid {name} settings, must be a table"));
+    };
+    let sub = |key: &str| match settings.get(key) {
+        Some(Val::Table(sub)) => Ok(Some(content(sub, settings)?)),
+        Some(_) => Err(format!("Invalid {name}.{key} settings, must be a table")),
+        None => Ok(None),
+    };
+    Ok((content(settings, settings)?, sub("first")?, sub("even")?))
+}
+
+fn content<'a>(settings: &'a toml::Table, base: &'a toml::Table) -> Result<Content<'a>, String> {
+    use docx::AlignmentType;
+
+    let get = |key: &str| settings.get(key).or_else(|| base.get(key));
+    let text = match get("text") {
+        Some(Val::String(text)) => Some(text.as_str()),
+        Some(_) => return Err("Header and footer text must be a string".into()),
+        None => None,
+    };
+    let align = match get("align") {
+        Some(Val::String(align)) => Some(match align.as_str() {
+            "center" | "c" => AlignmentType::Center,
+            "left" | "l" => AlignmentType::Left,
+            "right" | "r" => AlignmentType::Right,
+            _ => return Err(format!("Invalid alignment: {align}")),
+        }),
+        Some(_) => return Err("align must be a string".into()),
+        None => None,
+    };
+    Ok(Content { text, align })
+}
+
+impl Content<'_> {
+    fn paragraph(&self, title: &str) -> docx::Paragraph {
+        let mut p = docx::Paragraph::new();
+        if let Some(text) = self.text {
+            p.children.extend(
+                to_runs(&expand_text(text), title)
+                    .into_iter()
+                    .map(|r| docx::ParagraphChild::Run(Box::new(r))),
+            );
+        }
+        match self.align {
+            Some(align) => p.align(align),
+            None => p,
+        }
+    }
+}
+
+/// 将 `{page}`、`{pages}`、`{title}` 和 `{chapter}` 占位符展开为文本或域，`{{ name }}` 变量已在之前替换
+///
+/// 其他花括号原样保留为文字。
+fn to_runs(text: &str, title: &str) -> Vec<Run> {
+    use docx::{InstrPAGE, InstrText};
+
+    let mut ans = Vec::new();
+    let mut literal = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        literal.push_str(&rest[..start]);
+        let instr = match &rest[start + 1..start + len] {
+            "page" => Some(InstrText::PAGE(InstrPAGE::new())),
+            "pages" => Some(InstrText::Unsupported("NUMPAGES".into())),
+            "chapter" => Some(InstrText::Unsupported(format!(
+                "STYLEREF \"{}\"",
+                HEADING_STYLE_NAME(1)
+            ))),
+            "title" => {
+                literal.push_str(title);
+                None
+            }
+            _ => {
+                literal.push_str(&rest[start..=start + len]);
+                None
+            }
+        };
+        if let Some(instr) = instr {
+            if !literal.is_empty() {
+                ans.push(Run::new().add_text(std::mem::take(&mut literal)));
+            }
+            ans.extend(field(instr));
+        }
+        rest = &rest[start + len + 1..];
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        ans.push(Run::new().add_text(literal));
+    }
+    ans
+}
+
+fn field(instr: docx::InstrText) -> [Run; 4] {
+    use docx::FieldCharType::{Begin, End, Separate};
+    [
+        Run::new().add_field_char(Begin, false),
+        Run::new().add_instr_text(instr),
+        Run::new().add_field_char(Separate, false),
+        Run::new().add_field_char(End, false),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use docx::{InstrText, RunChild};
+
+    /// 各段文字，域显示为 `[域代码]`
+    fn runs(text: &str) -> Vec<String> {
+        to_runs(text, "标题")
+            .into_iter()
+            .flat_map(|run| run.children)
+            .filter_map(|child| match child {
+                RunChild::Text(text) => Some(text.text),
+                RunChild::InstrText(instr) => Some(match *instr {
+                    InstrText::PAGE(_) => "[PAGE]".into(),
+                    InstrText::Unsupported(code) => format!("[{code}]"),
+                    _ => unreachable!(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn expands_placeholders() {
+        assert_eq!(
+            runs("第 {page} 页，共 {pages} 页"),
+            ["第 ", "[PAGE]", " 页，共 ", "[NUMPAGES]", " 页"]
+        );
+        assert_eq!(
+            runs("{title} - {chapter}"),
+            ["标题 - ", "[STYLEREF \"Heading 1\"]"]
+        );
+    }
+
+    #[test]
+    fn keeps_other_braces_literal() {
+        assert_eq!(runs("{draft} {page}"), ["{draft} ", "[PAGE]"]);
+        assert_eq!(runs("{} x {"), ["{} x {"]);
+        assert_eq!(runs("a } b"), ["a } b"]);
+    }
+}
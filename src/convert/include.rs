@@ -0,0 +1,259 @@
+//! 包含其他 Markdown 文件，以及按 mdBook 的 `SUMMARY.md` 合并多个文件
+//!
+//! `<!-- include: ch2.md -->` 和 `{{#include ch2.md}}` 单独成段，可以加 `shift=1` 使被包含文件的标题降低一级。
+
+use super::{
+    code,
+    directive::{self, INCLUDE},
+    md, Ast,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+};
+
+/// 正在包含的文件，用于发现循环包含
+static INCLUDING: LazyLock<Mutex<Vec<PathBuf>>> = LazyLock::new(Default::default);
+
+/// 将包含指令替换为被包含文件的内容，`load` 读取并预处理一个文件
+pub fn expand(children: Vec<Ast>, file: &Path, load: &dyn Fn(&Path) -> md::Root) -> Vec<Ast> {
+    let dir = file.parent().unwrap();
+    let mut ans = Vec::with_capacity(children.len());
+    for ast in children {
+        match include(&ast) {
+            Some((path, shift)) => ans.extend(load_file(&dir.join(path), shift, load)),
+            None => ans.push(ast),
+        }
+    }
+    ans
+}
+
+/// 文件名为 `SUMMARY.md` 时按 mdBook 的目录合并各章
+pub fn is_summary(file: &Path) -> bool {
+    file.file_name().is_some_and(|name| name == "SUMMARY.md")
+}
+
+/// 按目录中链接的顺序合并各章，嵌套的章节标题按层级降低
+///
+/// 目录的一级标题（如 `# Summary`）、分隔线和没有链接的草稿章节被忽略，其他标题作为部分标题保留。
+pub fn summary(children: Vec<Ast>, file: &Path, load: &dyn Fn(&Path) -> md::Root) -> Vec<Ast> {
+    let dir = file.parent().unwrap();
+    let mut ans = Vec::new();
+    for ast in children {
+        match ast {
+            Ast::Heading(heading) if heading.depth == 1 && ans.is_empty() => {}
+            Ast::Heading(heading) => ans.push(Ast::Heading(heading)),
+            Ast::ThematicBreak(_) => {}
+            ast => chapters(&ast, 0, &mut |path, shift| {
+                ans.extend(load_file(&dir.join(path), shift, load))
+            }),
+        }
+    }
+    ans
+}
+
+/// 目录中的章节链接及其嵌套层级
+fn chapters(ast: &Ast, depth: u8, f: &mut impl FnMut(&str, u8)) {
+    match ast {
+        Ast::Link(md::Link { url, .. }) if !url.is_empty() => f(url, depth),
+        Ast::List(md::List { children, .. }) => {
+            // 列表项中的列表是子章节
+            for item in children {
+                for child in item.children().into_iter().flatten() {
+                    let depth = if matches!(child, Ast::List(_)) {
+                        depth + 1
+                    } else {
+                        depth
+                    };
+                    chapters(child, depth, f);
+                }
+            }
+        }
+        ast => {
+            for child in ast.children().into_iter().flatten() {
+                chapters(child, depth, f);
+            }
+        }
+    }
+}
+
+/// 解析包含指令，返回文件路径和标题降低的级数
+fn include(ast: &Ast) -> Option<(&str, u8)> {
+    let args = match (directive::comment(ast), ast) {
+        (Some(comment), _) => comment.strip_prefix(INCLUDE)?.strip_prefix(':')?,
+        (None, Ast::Paragraph(md::Paragraph { children, .. })) => {
+            let [Ast::Text(md::Text { value, .. })] = &children[..] else {
+                return None;
+            };
+            value
+                .trim()
+                .strip_prefix("{{#")?
+                .strip_prefix(INCLUDE)?
+                .strip_suffix("}}")?
+        }
+        _ => return None,
+    };
+    let mut words = args.split_whitespace();
+    let path = words
+        .next()
+        .unwrap_or_else(|| panic!("Include directive requires a file"));
+    let mut shift = 0;
+    for word in words {
+        match word.split_once('=') {
+            Some(("shift", n)) => {
+                shift = n
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid heading shift: {n}"))
+            }
+            _ => panic!("Unknown include option: {word}"),
+        }
+    }
+    Some((path, shift))
+}
+
+/// 读取被包含的文件，去掉 front matter，降低标题级别，并将图片和代码文件的路径改为相对于被包含的文件
+fn load_file(path: &Path, shift: u8, load: &dyn Fn(&Path) -> md::Root) -> Vec<Ast> {
+    let canonical = path
+        .canonicalize()
+        .unwrap_or_else(|e| panic!("Failed to include {}: {e}", path.display()));
+    {
+        let mut including = INCLUDING.lock().unwrap();
+        if including.contains(&canonical) {
+            let chain = including
+                .iter()
+                .chain([&canonical])
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            drop(including);
+            panic!("Circular include: {chain}");
+        }
+        including.push(canonical.clone());
+    }
+    let root = load(path);
+    INCLUDING.lock().unwrap().pop();
+
+    let mut children = root
+        .children
+        .into_iter()
+        .filter(|ast| !matches!(ast, Ast::Toml(_) | Ast::Yaml(_)))
+        .collect::<Vec<_>>();
+    rebase(&mut children, canonical.parent().unwrap(), shift);
+    children
+}
+
+fn rebase(nodes: &mut [Ast], dir: &Path, shift: u8) {
+    for node in nodes {
+        match node {
+            Ast::Heading(heading) => heading.depth = heading.depth.saturating_add(shift).min(6),
+            Ast::Image(image) if !image.url.contains("://") => {
+                image.url = dir.join(&image.url).to_string_lossy().into_owned()
+            }
+            // 没有语言时 `file=` 也可能是信息串的第一个词
+            Ast::Code(md::Code { lang, meta, .. }) => {
+                for info in [lang, meta].into_iter().flatten() {
+                    *info = code::rebase(info, dir);
+                }
+            }
+            _ => {}
+        }
+        if let Some(children) = node.children_mut() {
+            rebase(children, dir, shift);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::parse;
+    use std::fs;
+
+    /// 在临时目录中写入各文件，返回目录，每个测试使用不同的 `name`
+    fn book(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("md2docx-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, text) in files {
+            fs::write(dir.join(name), text).unwrap();
+        }
+        dir
+    }
+
+    fn load(path: &Path) -> md::Root {
+        parse(&fs::read_to_string(path).unwrap())
+    }
+
+    #[test]
+    fn merges_chapters_in_summary_order() {
+        let summary = "# Summary\n\n[Intro](intro.md)\n\n- [One](one.md)\n    - [Sec](sec.md)\n- [Draft]()\n\n---\n\n# Part\n\n- [Two](two.md)\n";
+        let dir = book(
+            "summary",
+            &[
+                ("SUMMARY.md", summary),
+                ("intro.md", "# Intro"),
+                ("one.md", "# One\n\ntext"),
+                ("sec.md", "# Sec\n\n![i](a.png)"),
+                ("two.md", "---\ntitle: x\n---\n\n# Two"),
+            ],
+        );
+        let file = dir.join("SUMMARY.md");
+        let children = super::summary(load(&file).children, &file, &load);
+
+        let headings = children
+            .iter()
+            .filter_map(|ast| match ast {
+                Ast::Heading(heading) => Some((ast.to_string(), heading.depth)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let expected = [
+            ("Intro", 1),
+            ("One", 1),
+            ("Sec", 2),
+            ("Part", 1),
+            ("Two", 1),
+        ];
+        assert_eq!(
+            headings,
+            expected.map(|(text, depth)| (text.to_string(), depth))
+        );
+
+        let Ast::Paragraph(md::Paragraph {
+            children: image, ..
+        }) = &children[4]
+        else {
+            panic!("expected the image paragraph");
+        };
+        let [Ast::Image(image)] = &image[..] else {
+            panic!("expected an image");
+        };
+        let canonical = dir.canonicalize().unwrap();
+        assert_eq!(Path::new(&image.url), canonical.join("a.png"));
+        assert_eq!(children.len(), 7);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn shifts_headings_up_to_level_six() {
+        let dir = book(
+            "shift",
+            &[
+                (
+                    "a.md",
+                    "<!-- include: b.md shift=255 -->\n\n{{#include b.md shift=1}}",
+                ),
+                ("b.md", "# One\n\n###### Six"),
+            ],
+        );
+        let file = dir.join("a.md");
+        let depths = expand(load(&file).children, &file, &load)
+            .iter()
+            .map(|ast| match ast {
+                Ast::Heading(heading) => heading.depth,
+                _ => 0,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(depths, [6, 6, 2, 6]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
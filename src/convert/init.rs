@@ -0,0 +1,226 @@
+//! 生成带注释的样式文件，列出转换时会用到的样式及其默认值
+
+use super::{
+    header_footer::HEADER_FOOTER_KEYS,
+    section::{PAGE_KEYS, PAGE_NUMBER_KEYS},
+    style::{
+        BASE_CODE_STYLE_ID, BODY_TEXT_STYLE_ID, BUILTIN_STYLE_IDS, CODE_PARAGRAPH_DEFAULTS,
+        CODE_STYLE_ID, DIFF_DEFAULTS, FONT_KEYS, HEADING_STYLE_ID, QUOTE_DEFAULTS, QUOTE_STYLE_ID,
+        STYLE_KEYS,
+    },
+};
+use std::fmt::Write;
+
+/// 不指定 Markdown 文件时列出的样式：所有内置样式，标题、各语言代码和嵌套引用的样式各举几例
+fn all_styles() -> Vec<String> {
+    let mut ids = Vec::new();
+    for &id in BUILTIN_STYLE_IDS {
+        ids.push(id.to_string());
+        match id {
+            BODY_TEXT_STYLE_ID => ids.extend((1..=4).map(HEADING_STYLE_ID)),
+            BASE_CODE_STYLE_ID => ids.push(CODE_STYLE_ID("rust")),
+            _ if id == QUOTE_STYLE_ID(1) => ids.push(QUOTE_STYLE_ID(2)),
+            _ => {}
+        }
+    }
+    ids
+}
+
+/// 生成样式文件，`ids` 为 `None` 时列出所有样式以及页面设置
+pub fn style_file(ids: Option<&[String]>) -> String {
+    let mut file = String::new();
+    let keys = |keys: &[&str]| keys.join(", ");
+    writeln!(file, "# md2docx 样式文件，取值和单位见 README").unwrap();
+    writeln!(file, "#").unwrap();
+    writeln!(file, "# 样式中可用的设置：").unwrap();
+    for line in wrap(&keys(STYLE_KEYS)) {
+        writeln!(file, "#   {line}").unwrap();
+    }
+    writeln!(file, "# 表形式的 font 中可用的设置：").unwrap();
+    for line in wrap(&keys(FONT_KEYS)) {
+        writeln!(file, "#   {line}").unwrap();
+    }
+
+    let all = all_styles();
+    for id in ids.unwrap_or(&all) {
+        writeln!(file).unwrap();
+        style(&mut file, id);
+    }
+    if ids.is_none() {
+        settings(&mut file);
+    }
+    file
+}
+
+fn style(file: &mut String, id: &str) {
+    let (description, defaults, examples): (String, Vec<&str>, Vec<&str>) = match id {
+        "BodyText" => (
+            "正文，其他段落样式默认基于正文".into(),
+            vec![],
+            vec![
+                r#"font = { east-asia = "宋体", ascii = "Times New Roman" }"#,
+                r#"font-size = "五号""#,
+                "first-line-indent = 2",
+                "line-spacing = 1.5",
+            ],
+        ),
+        "InlineCode" => (
+            "行内代码，字符样式".into(),
+            vec![],
+            vec![r#"font = "Consolas""#, r#"shading = "EEEEEE""#],
+        ),
+        "Code" => (
+            "代码块，各语言的代码块样式基于此样式".into(),
+            vec![r#"based-on = "BodyText""#, r#"align = "left""#],
+            [
+                r#"font = "Consolas""#,
+                r#"font-size = "小五""#,
+                r#"layout = "table"  # table 或 paragraphs，paragraphs 默认带有以下底色和边框"#,
+            ]
+            .into_iter()
+            .chain(defaults_of(CODE_PARAGRAPH_DEFAULTS, id))
+            .chain([
+                "keep-together = true  # 代码块不跨页",
+                r#"syntax-theme = "InspiredGitHub"  # 语法高亮主题，none 表示不高亮"#,
+                "line-numbers = true  # 默认显示行号",
+                r#"line-highlight = "FFF2CC"  # 突出显示的行的底色"#,
+                "tab-width = 4  # 制表符展开的宽度，0 表示保留制表符",
+                "wrap-at = 80  # 超过此宽度的行拆开并加折行标记",
+                r#"wrap-marker = "↪""#,
+            ])
+            .collect(),
+        ),
+        "CodeLineNumber" => (
+            "代码块的行号，字符样式".into(),
+            vec![r#"color = "808080""#],
+            vec![],
+        ),
+        "DiffInsert" | "DiffDelete" | "DiffHunk" => (
+            format!(
+                "差异代码块中{}",
+                match id {
+                    "DiffInsert" => "增加的行",
+                    "DiffDelete" => "删除的行",
+                    _ => "以 @@ 开头的块头和文件头",
+                }
+            ),
+            [r#"based-on = "Code-diff""#]
+                .into_iter()
+                .chain(defaults_of(DIFF_DEFAULTS, id))
+                .collect(),
+            vec![],
+        ),
+        "Image" => ("图片所在的段落".into(), vec![r#"align = "center""#], vec![]),
+        "Table" => (
+            "表格，表格样式".into(),
+            vec![r#"based-on = "BodyText""#, r#"align = "center""#],
+            vec![],
+        ),
+        "Caption" => (
+            "图片和代码块的题注".into(),
+            vec![r#"based-on = "BodyText""#, r#"align = "center""#],
+            vec![r#"font-size = "小五""#],
+        ),
+        "Quote" => (
+            "引用，嵌套的引用依次使用 Quote2、Quote3 等样式".into(),
+            [r#"based-on = "BodyText""#]
+                .into_iter()
+                .chain(defaults_of(QUOTE_DEFAULTS, id))
+                .collect(),
+            vec![r#"layout = "paragraphs"  # table 表示排版为单格表格"#],
+        ),
+        "Header" | "Footer" => (
+            format!(
+                "{}段落，内容在 [{}] 中设置",
+                if id == "Header" { "页眉" } else { "页脚" },
+                id.to_lowercase()
+            ),
+            vec![r#"based-on = "BodyText""#, r#"align = "center""#],
+            vec![r#"font-size = "小五""#],
+        ),
+        _ => match (
+            id.strip_prefix("Heading"),
+            id.strip_prefix("Code-"),
+            id.strip_prefix("Quote"),
+        ) {
+            (Some(depth), _, _) => (
+                format!("{depth} 级标题"),
+                vec![r#"based-on = "BodyText""#],
+                vec![r#"font = "黑体""#, "keep-with-next = true"],
+            ),
+            (_, Some(""), _) => (
+                "未标注语言的代码块".into(),
+                vec![r#"based-on = "Code""#],
+                vec![],
+            ),
+            (_, Some(lang), _) => (
+                format!("{lang} 代码块"),
+                vec![r#"based-on = "Code""#],
+                vec![],
+            ),
+            (_, _, Some(depth)) if depth.parse::<u8>().is_ok() => (
+                format!("第 {depth} 层引用，默认基于上一层，缩进为 Quote 的 {depth} 倍"),
+                vec![],
+                vec![],
+            ),
+            _ => (format!("自定义样式 {id}"), vec![], vec![]),
+        },
+    };
+    writeln!(file, "# {description}").unwrap();
+    writeln!(file, "[{id}]").unwrap();
+    for line in defaults {
+        writeln!(file, "{line}").unwrap();
+    }
+    for line in examples {
+        writeln!(file, "# {line}").unwrap();
+    }
+}
+
+/// 内置样式的默认设置中 `[id]` 表的各行
+fn defaults_of<'a>(defaults: &'a str, id: &str) -> impl Iterator<Item = &'a str> {
+    let header = format!("[{id}]");
+    defaults
+        .lines()
+        .skip_while(move |line| *line != header)
+        .skip(1)
+        .take_while(|line| !line.starts_with('['))
+}
+
+/// 页面、页码和页眉页脚设置，均为注释
+fn settings(file: &mut String) {
+    let tables: [(&str, &str, &[&str]); 4] = [
+        ("page", "页面设置", PAGE_KEYS),
+        ("page-number", "页码", PAGE_NUMBER_KEYS),
+        (
+            "header",
+            "页眉，可用 {page}、{pages}、{title}、{chapter} 占位符",
+            HEADER_FOOTER_KEYS,
+        ),
+        ("footer", "页脚", HEADER_FOOTER_KEYS),
+    ];
+    for (name, description, keys) in tables {
+        writeln!(file).unwrap();
+        writeln!(file, "# {description}，可用的设置：").unwrap();
+        for line in wrap(&keys.join(", ")) {
+            writeln!(file, "#   {line}").unwrap();
+        }
+        writeln!(file, "# [{name}]").unwrap();
+    }
+}
+
+/// 将逗号分隔的列表折成不超过 80 列的行
+fn wrap(list: &str) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    for item in list.split(' ') {
+        let line = lines.last_mut().unwrap();
+        if !line.is_empty() && line.len() + item.len() + 1 > 76 {
+            lines.push(item.into());
+        } else {
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(item);
+        }
+    }
+    lines
+}
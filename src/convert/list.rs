@@ -11,7 +11,7 @@ pub fn from_list(list: md::List, dir: &Path) -> Vec<docx::Paragraph> {
     if ordered {
         assert!(matches!(start, Some(1)));
     } else {
-        assert!(matches!(start, None));
+        assert!(start.is_none());
     }
 
     let numbering = ListNumbering::new(ordered);
@@ -0,0 +1,32 @@
+use super::{md, Ast};
+use toml::Value as Val;
+
+/// 从文档开头的 front matter 读取元数据，支持 TOML 和简单的 YAML `key: value` 形式
+pub fn from_root(root: &md::Root) -> toml::Table {
+    match root.children.first() {
+        Some(Ast::Toml(md::Toml { value, .. })) => value
+            .parse()
+            .unwrap_or_else(|e| panic!("Failed to parse front matter: {e}")),
+        Some(Ast::Yaml(md::Yaml { value, .. })) => value
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(key, val)| {
+                let val = val.trim();
+                let val = val
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .or_else(|| val.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                    .unwrap_or(val);
+                (key.trim().to_string(), Val::String(val.into()))
+            })
+            .collect(),
+        _ => Default::default(),
+    }
+}
+
+pub fn title(metadata: &toml::Table) -> &str {
+    match metadata.get("title") {
+        Some(Val::String(title)) => title,
+        _ => "",
+    }
+}
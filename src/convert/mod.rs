@@ -1,36 +1,83 @@
-﻿mod list;
+mod check;
+mod code;
+mod condition;
+mod directive;
+mod header_footer;
+mod include;
+mod init;
+mod list;
+mod metadata;
 mod numbering;
 mod paragraph;
+mod preset;
 mod root;
+mod section;
 mod style;
 mod table;
+mod template;
 mod text;
+mod unit;
+mod variable;
+mod xml;
 
 use docx_rs as docx;
+use header_footer::add_header_footer;
 use markdown::{
     mdast::{self as md, Node as Ast},
-    to_mdast, ParseOptions,
+    to_mdast, Constructs, ParseOptions,
 };
 use numbering::add_numbering;
 use std::{
-    fs::{read_to_string, File},
-    path::PathBuf,
+    fs::{self, read_to_string, File},
+    io::Cursor,
+    path::{Path, PathBuf},
 };
 use style::add_style;
 
+pub use check::check_files as check_style_files;
+pub use condition::{filter as filter_profiles, profiles};
+pub use init::style_file;
+pub use preset::{base as preset_settings, Preset};
+
 #[derive(Args, Default)]
 pub struct Args {
     /// The file to convert
     file: PathBuf,
-    /// A Toml style file
+    /// Toml style files, later files override earlier ones
+    #[clap(short, long)]
+    style: Vec<PathBuf>,
+    /// A built-in style preset, overridden by the style files
+    #[clap(short, long, value_enum)]
+    preset: Option<preset::Preset>,
+    /// A docx whose styles, numbering, theme, headers, footers and page setup are reused
     #[clap(short, long)]
-    style: Option<PathBuf>,
+    reference_doc: Option<PathBuf>,
+    /// A docx whose body is kept, with the converted content inserted at the placeholder
+    #[clap(short, long, conflicts_with = "reference_doc")]
+    template: Option<PathBuf>,
+    /// The placeholder paragraph text or bookmark name in the template
+    #[clap(long, default_value = "{{content}}")]
+    placeholder: String,
+    /// Template variables, override the front matter and environment variables
+    #[clap(long = "var", value_name = "KEY=VALUE")]
+    vars: Vec<String>,
+    /// Enabled profiles, conditional blocks for other profiles are dropped
+    #[clap(long = "profile", value_name = "PROFILE")]
+    profiles: Vec<String>,
 }
 
 impl Args {
     pub fn convert(self) {
-        let Self { file, style } = self;
-        let dir = file.parent().unwrap();
+        let Self {
+            file,
+            style,
+            preset,
+            reference_doc,
+            template,
+            placeholder,
+            vars,
+            profiles,
+        } = self;
         let name = file
             .file_name()
             .unwrap()
@@ -38,29 +85,115 @@ impl Args {
             .unwrap()
             .strip_suffix(".md")
             .unwrap();
-        let style = style
-            .map(|p| {
-                read_to_string(&p)
-                    .unwrap_or_else(|e| panic!("Failed to read style file: {e}"))
-                    .parse::<toml::Table>()
-                    .unwrap_or_else(|e| {
-                        panic!("Failed to parse style file, must be in TOML format: {e}")
-                    })
-            })
-            .unwrap_or_default();
-
-        let md = read_to_string(&file).unwrap();
-        let Ok(Ast::Root(root)) = to_mdast(&md, &ParseOptions::gfm()) else {
-            panic!("Failed to parse markdown");
+        let style = preset::load(preset, &style);
+        let template = match (reference_doc, template) {
+            (Some(path), _) => Some(template::Template::open(&path)),
+            (_, Some(path)) => Some(template::Template::open(&path).fill_at(placeholder)),
+            _ => None,
         };
-        let docx = root::from_root(root, dir);
+        let docx = build(&file, &style, &vars, &profiles);
+        // 样式文件中设置过的属性覆盖模板中的同名样式
+        let overridden = style
+            .keys()
+            .map(|id| (id.clone(), style::set_properties(id, &style)))
+            .filter(|(_, properties)| !properties.is_empty())
+            .collect::<Vec<_>>();
         let docx = add_style(docx, style);
         let docx = add_numbering(docx);
-
+        let docx = section::add_grid_font_size(docx);
         let name = find_available_name(name);
-        let file = File::create(name).unwrap();
-        docx.build().pack(file).unwrap();
+        let mut xml = docx.build();
+        if let Some(template) = &template {
+            template.apply_page_setup(&mut xml.document);
+        }
+        section::apply_sections(&mut xml.document);
+        style::apply_highlighted_lines(&mut xml.document, &code::line_highlight());
+        style::apply_style_patches(&mut xml.styles);
+        match template {
+            Some(template) => {
+                // 合并出错时不留下不完整的文件
+                let mut buf = Cursor::new(Vec::new());
+                template.pack(xml, &overridden, &mut buf);
+                fs::write(name, buf.into_inner()).unwrap();
+            }
+            None => xml.pack(File::create(name).unwrap()).unwrap(),
+        }
+    }
+}
+
+/// 预处理并转换 `file` 的内容，加上页眉页脚，此后用到的样式均已确定
+fn build(file: &Path, style: &toml::Table, vars: &[String], profiles: &[String]) -> docx::Docx {
+    let md = read_to_string(file).unwrap();
+    let root = parse(&md);
+    let metadata = metadata::from_root(&root);
+    variable::init(&metadata, vars);
+    let root = preprocess(file, &md, root, profiles);
+    section::init(style);
+    style::init(style);
+    code::init(style);
+    let docx = root::from_root(root, file.parent().unwrap());
+    add_header_footer(docx, style, &metadata)
+}
+
+/// 替换变量，删除未启用的条件块，展开包含的文件，`root` 是 `md` 未经处理的解析结果
+fn preprocess(file: &Path, md: &str, root: md::Root, profiles: &[String]) -> md::Root {
+    // 删除的条件块中的变量可以未定义
+    let dropped = condition::dropped(&root.children, profiles);
+    let mut root = parse(&variable::expand_markdown(md, &root, dropped, file));
+    let children = condition::filter(root.children, profiles);
+    let load = |path: &Path| {
+        let md = read_to_string(path)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {e}", path.display()));
+        preprocess(path, &md, parse(&md), profiles)
+    };
+    root.children = if include::is_summary(file) {
+        include::summary(children, file, &load)
+    } else {
+        include::expand(children, file, &load)
+    };
+    root
+}
+
+fn parse(md: &str) -> md::Root {
+    let options = ParseOptions {
+        constructs: Constructs {
+            frontmatter: true,
+            ..Constructs::gfm()
+        },
+        ..ParseOptions::gfm()
+    };
+    let parse = |md: &str| {
+        let Ok(Ast::Root(root)) = to_mdast(md, &options) else {
+            panic!("Failed to parse markdown");
+        };
+        root
+    };
+    let root = parse(md);
+
+    // 与其他行相连的 `:::` 行换成等长的空白后重新解析，位置不变，再作为单独的段落放回
+    let lines = directive::fence_lines(md, &root.children);
+    if lines.is_empty() {
+        return root;
+    }
+    let mut blanked = md.to_string();
+    for range in &lines {
+        blanked.replace_range(range.clone(), &" ".repeat(range.len()));
     }
+    let mut root = parse(&blanked);
+    directive::insert_fences(&mut root.children, md, lines);
+    root
+}
+
+/// 以同样的样式、变量和 profile 转换 `file` 时会生成的样式
+pub fn used_styles(
+    file: &Path,
+    preset: Option<Preset>,
+    styles: &[PathBuf],
+    vars: &[String],
+    profiles: &[String],
+) -> Vec<String> {
+    build(file, &preset::load(preset, styles), vars, profiles);
+    style::used_style_ids()
 }
 
 fn find_available_name(name: &str) -> PathBuf {
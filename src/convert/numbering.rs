@@ -6,7 +6,7 @@ use std::sync::{LazyLock, Mutex};
 
 const HEADING_NUMBERING: usize = 1;
 const UNORDERED_NUMBERING: usize = 2;
-static LIST_INFO: LazyLock<Mutex<Vec<bool>>> = LazyLock::new(|| Default::default());
+static LIST_INFO: LazyLock<Mutex<Vec<bool>>> = LazyLock::new(Default::default);
 
 /// 为标题添加多级列表编号，`depth` in 1..=6
 pub fn heading_numbering(p: docx::Paragraph, depth: u8) -> docx::Paragraph {
@@ -0,0 +1,66 @@
+//! 内置的样式预设，以及多个样式文件的合并
+
+use super::check::check_files;
+use std::{fs::read_to_string, path::PathBuf};
+
+/// 编译进程序的样式预设
+#[derive(ValueEnum, Clone, Copy)]
+pub enum Preset {
+    /// GB/T 9704 official document
+    Gbt9704,
+    /// Chinese thesis
+    Thesis,
+    /// Plain English report
+    Report,
+}
+
+impl Preset {
+    fn content(self) -> &'static str {
+        match self {
+            Self::Gbt9704 => include_str!("preset/gbt9704.toml"),
+            Self::Thesis => include_str!("preset/thesis.toml"),
+            Self::Report => include_str!("preset/report.toml"),
+        }
+    }
+}
+
+/// 依次合并预设和样式文件，后面的设置覆盖前面的设置
+///
+/// 样式文件有误时报告所有问题后退出。
+pub fn load(preset: Option<Preset>, styles: &[PathBuf]) -> toml::Table {
+    let mut settings = base(preset);
+    if !check_files(styles, &settings) {
+        std::process::exit(1);
+    }
+    for path in styles {
+        let style = read_to_string(path)
+            .unwrap_or_else(|e| panic!("Failed to read style file: {e}"))
+            .parse::<toml::Table>()
+            .unwrap_or_else(|e| panic!("Failed to parse style file, must be in TOML format: {e}"));
+        merge(&mut settings, style);
+    }
+    settings
+}
+
+/// 预设的设置，没有预设时为空
+pub fn base(preset: Option<Preset>) -> toml::Table {
+    preset.map_or_else(Default::default, |p| {
+        p.content()
+            .parse()
+            .unwrap_or_else(|e| panic!("Failed to parse preset: {e}"))
+    })
+}
+
+/// 深度合并，表逐键合并，其他值直接覆盖
+fn merge(base: &mut toml::Table, over: toml::Table) {
+    use toml::Value as Val;
+
+    for (key, val) in over {
+        match (base.get_mut(&key), val) {
+            (Some(Val::Table(base)), Val::Table(over)) => merge(base, over),
+            (_, val) => {
+                base.insert(key, val);
+            }
+        }
+    }
+}
@@ -1,36 +1,135 @@
-﻿use super::{
-    docx::{self, Docx},
+use super::{
+    directive::{self, Fence, COLUMNS, LANDSCAPE, MAIN_MATTER},
+    docx::Docx,
     list::from_list,
     md,
     paragraph::{from_heading, from_paragraph},
-    table::{from_block_quote, from_code, from_table},
+    section::{self, is_front_matter, is_wide_table, main_matter, Columns},
+    style::{custom_style, custom_table_style, is_custom_style},
+    table::{from_block_quote, from_code, from_table, Block},
     Ast,
 };
 use std::path::Path;
 
 pub fn from_root(root: md::Root, dir: &Path) -> Docx {
+    // 文档中有正文标记时，不再按标题自动判断正文的开始
+    let marked = root
+        .children
+        .iter()
+        .any(|ast| directive::comment(ast) == Some(MAIN_MATTER));
+
     let mut docx = Docx::new();
+    // 每层 `:::` 块结束时恢复的版面，以及块中使用的自定义样式
+    let mut fences: Vec<(section::Section, Option<String>)> = Vec::new();
     for node in root.children {
+        if let Some(fence) = directive::fence(&node) {
+            let paragraph = match fence {
+                Fence::Open(attrs) => {
+                    let last = section::current();
+                    let mut next = last.clone();
+                    if attrs.has(LANDSCAPE) {
+                        next = next.landscape(true);
+                    }
+                    if attrs.has(COLUMNS) {
+                        next = next.columns(Columns::from_attributes(&attrs));
+                    }
+                    let style = attrs
+                        .get("style")
+                        .map(str::to_string)
+                        .or_else(|| attrs.classes.iter().find(|c| is_custom_style(c)).cloned());
+                    fences.push((last, style));
+                    section::switch(next)
+                }
+                Fence::Close => fences.pop().and_then(|(last, _)| section::switch(last)),
+            };
+            if let Some(p) = paragraph {
+                docx = docx.add_paragraph(p);
+            }
+            continue;
+        }
+        let style = fences.iter().rev().find_map(|(_, style)| style.as_deref());
+        let styled = |p| match style {
+            Some(id) => custom_style(p, id),
+            None => p,
+        };
+        let styled_table = |t| match style {
+            Some(id) => custom_table_style(t, id),
+            None => t,
+        };
+
         docx = match node {
-            Ast::Heading(heading) => docx.add_paragraph(from_heading(heading)),
+            Ast::Heading(heading) => {
+                if heading.depth == 1
+                    && !marked
+                    && !is_front_matter(
+                        &heading
+                            .children
+                            .iter()
+                            .map(Ast::to_string)
+                            .collect::<String>(),
+                    )
+                {
+                    if let Some(p) = main_matter() {
+                        docx = docx.add_paragraph(p);
+                    }
+                }
+                docx.add_paragraph(from_heading(heading))
+            }
             Ast::Paragraph(paragraph) => {
                 let (paragraph, caption) = from_paragraph(paragraph, dir);
                 match caption {
                     Some(c) => docx.add_paragraph(paragraph).add_paragraph(c),
-                    None => docx.add_paragraph(paragraph),
+                    None => docx.add_paragraph(styled(paragraph)),
                 }
             }
             Ast::Code(code) => {
-                let (code, caption) = from_code(code);
-                docx.add_table(code).add_paragraph(caption)
+                let (code, caption) = from_code(code, dir);
+                match code {
+                    Block::Table(table) => docx.add_table(*table),
+                    Block::Paragraphs(lines) => lines
+                        .into_iter()
+                        .fold(docx, |docx, p| docx.add_paragraph(p)),
+                }
+                .add_paragraph(caption)
+            }
+            Ast::BlockQuote(quote) => {
+                from_block_quote(quote, dir)
+                    .into_iter()
+                    .fold(docx, |docx, block| match block {
+                        Block::Table(table) => docx.add_table(*table),
+                        Block::Paragraphs(lines) => lines
+                            .into_iter()
+                            .fold(docx, |docx, p| docx.add_paragraph(p)),
+                    })
             }
-            Ast::BlockQuote(quote) => docx
-                .add_table(from_block_quote(quote, dir))
-                .add_paragraph(docx::Paragraph::new()),
-            Ast::Table(table) => docx.add_table(from_table(table)),
+            Ast::Table(table) if is_wide_table(table.align.len()) => {
+                let last = section::current();
+                match section::switch(last.clone().landscape(true)) {
+                    Some(p) => docx
+                        .add_paragraph(p)
+                        .add_table(styled_table(from_table(table)))
+                        .add_paragraph(section::switch(last).unwrap()),
+                    None => docx.add_table(styled_table(from_table(table))),
+                }
+            }
+            Ast::Table(table) => docx.add_table(styled_table(from_table(table))),
             Ast::List(list) => from_list(list, dir)
                 .into_iter()
-                .fold(docx, |docx, p| docx.add_paragraph(p)),
+                .fold(docx, |docx, p| docx.add_paragraph(styled(p))),
+            // front matter 已作为元数据读取
+            Ast::Toml(_) | Ast::Yaml(_) => docx,
+            ref html @ Ast::Html(_) => match directive::comment(html) {
+                Some(MAIN_MATTER) => match main_matter() {
+                    Some(p) => docx.add_paragraph(p),
+                    None => docx,
+                },
+                Some(_) => docx,
+                // 其他 HTML 无法转换，跳过
+                None => {
+                    eprintln!("Skipped unsupported HTML: {}", html.to_string().trim());
+                    docx
+                }
+            },
 
             Ast::Root(_)
             | Ast::Text(_)
@@ -47,9 +146,6 @@ pub fn from_root(root: md::Root, dir: &Path) -> Docx {
             | Ast::TableCell(_) => unreachable!(),
 
             Ast::ThematicBreak(_)
-            | Ast::Html(_)
-            | Ast::Toml(_)
-            | Ast::Yaml(_)
             | Ast::Math(_)
             | Ast::Break(_)
             | Ast::InlineMath(_)
@@ -0,0 +1,641 @@
+use super::{
+    directive::Attributes,
+    docx::{self, Docx},
+    style::font_size,
+    unit::{twips, Length, Unit},
+    xml,
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering::Relaxed},
+    LazyLock, Mutex,
+};
+use toml::Value as Val;
+
+const SECTION_MARKER: fn(usize) -> String = |i| format!("md2docx-section-{i}");
+/// `[page]` 中的设置
+pub const PAGE_KEYS: &[&str] = &[
+    "size",
+    "width",
+    "height",
+    "margin-top",
+    "margin-bottom",
+    "margin-left",
+    "margin-right",
+    "grid",
+    "lines-per-page",
+    "chars-per-line",
+    "line-pitch",
+    "landscape-table-columns",
+    "columns",
+    "column-space",
+    "column-separator",
+];
+/// `[page-number]` 中的设置
+pub const PAGE_NUMBER_KEYS: &[&str] = &["format", "front-matter", "start", "front-matter-headings"];
+pub const SECTION_PROPERTY_ORDER: &[&str] = &[
+    "w:headerReference",
+    "w:footerReference",
+    "w:footnotePr",
+    "w:endnotePr",
+    "w:type",
+    "w:pgSz",
+    "w:pgMar",
+    "w:paperSrc",
+    "w:pgBorders",
+    "w:lnNumType",
+    "w:pgNumType",
+    "w:cols",
+    "w:formProt",
+    "w:vAlign",
+    "w:noEndnote",
+    "w:titlePg",
+    "w:textDirection",
+    "w:bidi",
+    "w:rtlGutter",
+    "w:docGrid",
+    "w:printerSettings",
+    "w:sectPrChange",
+];
+const FRONT_MATTER_HEADINGS: &[&str] = &[
+    "摘要", "Abstract", "目录", "Contents", "前言", "Preface", "序言",
+];
+
+/// 一节的页面设置
+#[derive(Clone, Default)]
+pub struct Section {
+    /// 纵向时的纸张宽度和高度，单位为 twip
+    size: (Option<usize>, Option<usize>),
+    /// 纵向时的上、右、下、左页边距，单位为 twip
+    margins: [Option<usize>; 4],
+    /// 页码格式和起始页码
+    page_number: Option<(String, Option<u32>)>,
+    /// 横向页面
+    landscape: bool,
+    /// 分栏
+    columns: Option<Columns>,
+    /// 与上一节在同一页上接续，而不是从新页开始
+    continuous: bool,
+    /// 文档网格
+    grid: Option<Grid>,
+}
+
+/// 文档网格设置
+#[derive(Clone, Default)]
+struct Grid {
+    /// 网格类型
+    kind: Option<String>,
+    /// 每页行数，由版心高度计算行距
+    lines: Option<usize>,
+    /// 行距，单位为 twip
+    line_pitch: Option<usize>,
+    /// 每行字数，由版心宽度计算字距
+    chars: Option<usize>,
+    /// 计算字距所用的字号，单位为磅
+    font_size: f64,
+}
+
+/// 分栏设置
+#[derive(Clone, PartialEq)]
+pub struct Columns {
+    /// 栏数
+    num: usize,
+    /// 栏间距，单位为 twip
+    space: Option<usize>,
+    /// 栏间分隔线
+    separator: bool,
+}
+
+/// 已结束的节，分节符段落通过下标引用
+static SECTIONS: LazyLock<Mutex<Vec<Section>>> = LazyLock::new(Default::default);
+/// 当前所在的节
+static CURRENT: LazyLock<Mutex<Section>> = LazyLock::new(Default::default);
+/// 设置了前置部分页码时，正文开始后的节
+static MAIN_MATTER: LazyLock<Mutex<Option<Section>>> = LazyLock::new(Default::default);
+static FRONT_MATTER: LazyLock<Mutex<Vec<String>>> = LazyLock::new(Default::default);
+/// 列数超过此值的表格自动放在横向页面中，0 表示不自动横向
+static LANDSCAPE_TABLE_COLUMNS: AtomicUsize = AtomicUsize::new(0);
+
+/// `[page]` 和 `[page-number]` 设置解析后的结果
+struct Page {
+    current: Section,
+    main_matter: Option<Section>,
+    front_matter: Option<Vec<String>>,
+    landscape_table_columns: Option<usize>,
+}
+
+/// 按样式文件中的 `[page]` 和 `[page-number]` 设置初始化节
+pub fn init(settings: &toml::Table) {
+    for (name, keys) in [("page", PAGE_KEYS), ("page-number", PAGE_NUMBER_KEYS)] {
+        if let Some(Val::Table(table)) = settings.get(name) {
+            for key in table.keys().filter(|k| !keys.contains(&k.as_str())) {
+                eprintln!("Unknown {name} setting: {key}");
+            }
+        }
+    }
+    let current = CURRENT.lock().unwrap().clone();
+    let page = parse(settings, current).unwrap_or_else(|e| panic!("{e}"));
+    *CURRENT.lock().unwrap() = page.current;
+    if let Some(main) = page.main_matter {
+        *MAIN_MATTER.lock().unwrap() = Some(main);
+    }
+    if let Some(headings) = page.front_matter {
+        *FRONT_MATTER.lock().unwrap() = headings;
+    }
+    if let Some(n) = page.landscape_table_columns {
+        LANDSCAPE_TABLE_COLUMNS.store(n, Relaxed);
+    }
+}
+
+/// 检查 `[page]` 和 `[page-number]` 设置，不改变当前的节
+pub fn check(settings: &toml::Table) -> Result<(), String> {
+    parse(settings, Section::default()).map(|_| ())
+}
+
+/// 在 `current` 的基础上解析页面设置
+fn parse(settings: &toml::Table, current: Section) -> Result<Page, String> {
+    let mut page = Page {
+        current,
+        main_matter: None,
+        front_matter: None,
+        landscape_table_columns: None,
+    };
+    if let Some(val) = settings.get("page") {
+        parse_page(val, settings, &mut page)?;
+    }
+    if let Some(val) = settings.get("page-number") {
+        parse_page_number(val, &mut page)?;
+    }
+    Ok(page)
+}
+
+fn parse_page(val: &Val, styles: &toml::Table, page: &mut Page) -> Result<(), String> {
+    let Val::Table(settings) = val else {
+        return Err("Invalid page settings, must be a table".into());
+    };
+    let current = &mut page.current;
+    // 纸张名称先于单独设置的宽度和高度
+    if let Some(val) = settings.get("size") {
+        let Val::String(name) = val else {
+            return Err("page.size must be a string".into());
+        };
+        let (w, h) = paper_size(name)?;
+        current.size = (Some(twips(w) as _), Some(twips(h) as _));
+    }
+    // 字距相对于正文字号计算，默认为五号字
+    let font_size = match styles.get("BodyText") {
+        Some(Val::Table(body)) => match body.get("font-size") {
+            Some(val) => font_size(val)?,
+            None => 10.5,
+        },
+        _ => 10.5,
+    };
+    for (key, val) in settings {
+        match key.as_str() {
+            "size" => {}
+            "width" | "height" | "margin-top" | "margin-bottom" | "margin-left"
+            | "margin-right" => {
+                let path = format!("page.{key}");
+                let pt = Length::points(val, &path)?;
+                let margin = key.starts_with("margin-");
+                if pt < 0. || (pt == 0. && !margin) {
+                    let bound = if margin { "non-negative" } else { "positive" };
+                    return Err(format!("{path} must be {bound}"));
+                }
+                let length = Some(twips(pt) as _);
+                match key.as_str() {
+                    "width" => current.size.0 = length,
+                    "height" => current.size.1 = length,
+                    "margin-top" => current.margins[0] = length,
+                    "margin-right" => current.margins[1] = length,
+                    "margin-bottom" => current.margins[2] = length,
+                    _ => current.margins[3] = length,
+                }
+            }
+            "grid" => match val {
+                Val::String(kind) => {
+                    current.grid(font_size).kind = Some(
+                        match kind.as_str() {
+                            "none" => "default",
+                            "lines" => "lines",
+                            "lines-and-chars" => "linesAndChars",
+                            "snap-to-chars" => "snapToChars",
+                            _ => return Err(format!("Invalid grid type: {kind}")),
+                        }
+                        .into(),
+                    )
+                }
+                _ => return Err("page.grid must be a string".into()),
+            },
+            "lines-per-page" => match *val {
+                Val::Integer(n) if n > 0 => current.grid(font_size).lines = Some(n as _),
+                _ => return Err("page.lines-per-page must be a positive integer".into()),
+            },
+            "chars-per-line" => match *val {
+                Val::Integer(n) if n > 0 => current.grid(font_size).chars = Some(n as _),
+                _ => return Err("page.chars-per-line must be a positive integer".into()),
+            },
+            "line-pitch" => match Length::points(val, "page.line-pitch")? {
+                pt if pt > 0. => current.grid(font_size).line_pitch = Some(twips(pt) as _),
+                _ => return Err("page.line-pitch must be positive".into()),
+            },
+            "landscape-table-columns" => match *val {
+                Val::Integer(n) if n > 0 => page.landscape_table_columns = Some(n as _),
+                _ => return Err("page.landscape-table-columns must be a positive integer".into()),
+            },
+            "columns" => match *val {
+                Val::Integer(num) if num > 0 => {
+                    let columns = current.columns.get_or_insert(Columns::new(1));
+                    columns.num = num as _;
+                }
+                _ => return Err("page.columns must be a positive integer".into()),
+            },
+            "column-space" => {
+                let space = match Length::points(val, "page.column-space")? {
+                    pt if pt >= 0. => pt,
+                    _ => return Err("page.column-space must be non-negative".into()),
+                };
+                let columns = current.columns.get_or_insert(Columns::new(1));
+                columns.space = Some(twips(space) as _);
+            }
+            "column-separator" => match *val {
+                Val::Boolean(separator) => {
+                    current.columns.get_or_insert(Columns::new(1)).separator = separator
+                }
+                _ => return Err("page.column-separator must be a boolean".into()),
+            },
+            // 未知的键在 `init` 中报告
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn parse_page_number(val: &Val, page: &mut Page) -> Result<(), String> {
+    let Val::Table(settings) = val else {
+        return Err("Invalid page-number settings, must be a table".into());
+    };
+    let format = |key: &str| match settings.get(key) {
+        Some(Val::String(format)) => Ok(Some(number_format(format))),
+        Some(_) => Err(format!("page-number.{key} must be a string")),
+        None => Ok(None),
+    };
+    let start = match settings.get("start") {
+        Some(&Val::Integer(start)) if start >= 0 => Some(start as _),
+        Some(_) => return Err("page-number.start must be a non-negative integer".into()),
+        None => None,
+    };
+    let format_main = format("format")?.unwrap_or("decimal".into());
+    let front_matter = format("front-matter")?;
+    let headings = match settings.get("front-matter-headings") {
+        Some(Val::Array(arr)) => arr
+            .iter()
+            .map(|val| match val {
+                Val::String(s) => Ok(s.clone()),
+                _ => Err("page-number.front-matter-headings must be strings".to_string()),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err("page-number.front-matter-headings must be an array".into()),
+        None => FRONT_MATTER_HEADINGS.iter().map(|&s| s.into()).collect(),
+    };
+
+    let current = &mut page.current;
+    match front_matter {
+        Some(front) => {
+            page.main_matter = Some(Section {
+                page_number: Some((format_main, start.or(Some(1)))),
+                ..current.clone()
+            });
+            current.page_number = Some((front, Some(1)));
+            page.front_matter = Some(headings);
+        }
+        None => current.page_number = Some((format_main, start)),
+    }
+    Ok(())
+}
+
+/// 常用纸张的宽度和高度，单位为磅
+fn paper_size(name: &str) -> Result<(f64, f64), String> {
+    let mm = |w: f64, h: f64| Ok((w * 72. / 25.4, h * 72. / 25.4));
+    match name {
+        "A3" => mm(297., 420.),
+        "A4" => mm(210., 297.),
+        "A5" => mm(148., 210.),
+        "B5" => mm(176., 250.),
+        "letter" => Ok((612., 792.)),
+        _ => Err(format!(
+            "Invalid page.size: {name}, must be A3, A4, A5, B5 or letter"
+        )),
+    }
+}
+
+fn number_format(format: &str) -> String {
+    match format {
+        "1" => "decimal",
+        "i" => "lowerRoman",
+        "I" => "upperRoman",
+        "a" => "lowerLetter",
+        "A" => "upperLetter",
+        "一" => "chineseCounting",
+        format => format,
+    }
+    .into()
+}
+
+/// 结束当前节，之后的内容属于 `next` 描述的新节
+pub fn section_break(next: Section) -> docx::Paragraph {
+    let last = std::mem::replace(&mut *CURRENT.lock().unwrap(), next);
+    let mut sections = SECTIONS.lock().unwrap();
+    sections.push(last);
+    docx::Paragraph::new().style(&SECTION_MARKER(sections.len() - 1))
+}
+
+/// 设置了每行字数时，将文档默认字号设为计算字距所用的字号
+pub fn add_grid_font_size(docx: Docx) -> Docx {
+    match &CURRENT.lock().unwrap().grid {
+        Some(grid) if grid.chars.is_some() => docx.default_size((grid.font_size * 2.) as _),
+        _ => docx,
+    }
+}
+
+/// 当前节的版面，用于派生新节或在块结束后恢复
+pub fn current() -> Section {
+    CURRENT.lock().unwrap().continued()
+}
+
+/// 切换到 `next` 的版面，版面不变时不分节。
+///
+/// 只改变分栏时，新节与上一节在同一页上接续。
+pub fn switch(next: Section) -> Option<docx::Paragraph> {
+    let current = CURRENT.lock().unwrap().clone();
+    if current.landscape == next.landscape && current.columns == next.columns {
+        None
+    } else {
+        Some(section_break(Section {
+            continuous: current.landscape == next.landscape,
+            ..next
+        }))
+    }
+}
+
+/// 表格列数超过设置值时需要横向页面
+pub fn is_wide_table(columns: usize) -> bool {
+    match LANDSCAPE_TABLE_COLUMNS.load(Relaxed) {
+        0 => false,
+        n => columns > n,
+    }
+}
+
+/// 设置了前置部分页码时，在正文开始处分节
+pub fn main_matter() -> Option<docx::Paragraph> {
+    MAIN_MATTER.lock().unwrap().take().map(section_break)
+}
+
+/// 不属于前置部分的一级标题是正文的开始
+pub fn is_front_matter(heading: &str) -> bool {
+    FRONT_MATTER
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|h| h == heading.trim())
+}
+
+/// 将分节符段落替换为实际的节属性。
+///
+/// 页眉页脚引用和首页不同等设置由 docx-rs 写入文档末尾的节属性，所有节共用。
+/// 首页不同（`w:titlePg`）只保留在文档的第一节，之后各节的第一页使用默认的页眉页脚。
+pub fn apply_sections(document: &mut Vec<u8>) {
+    let mut text = String::from_utf8(std::mem::take(document)).unwrap();
+    let body = xml::find(&text, "w:sectPr").expect("No section property in document");
+    let template = text[body.clone()].to_string();
+    let later = xml::remove_child(&template, "w:titlePg", SECTION_PROPERTY_ORDER);
+
+    let sections = SECTIONS.lock().unwrap();
+    let first = |i: usize| if i == 0 { &template } else { &later };
+    let last = CURRENT.lock().unwrap().build(first(sections.len()));
+    text.replace_range(body, &last);
+    for (i, section) in sections.iter().enumerate() {
+        text = text.replace(
+            &format!(r#"<w:pStyle w:val="{}" />"#, SECTION_MARKER(i)),
+            &section.build(first(i)),
+        );
+    }
+    *document = text.into_bytes();
+}
+
+impl Section {
+    fn grid(&mut self, font_size: f64) -> &mut Grid {
+        self.grid.get_or_insert_with(|| Grid {
+            font_size,
+            ..Default::default()
+        })
+    }
+
+    pub fn landscape(self, landscape: bool) -> Self {
+        Self { landscape, ..self }
+    }
+
+    pub fn columns(self, columns: Columns) -> Self {
+        Self {
+            columns: Some(columns),
+            ..self
+        }
+    }
+
+    /// 接续当前节的新节，页码不重新开始
+    fn continued(&self) -> Self {
+        let mut next = self.clone();
+        if let Some((_, start)) = &mut next.page_number {
+            *start = None;
+        }
+        next.continuous = false;
+        next
+    }
+
+    fn build(&self, template: &str) -> String {
+        let order = SECTION_PROPERTY_ORDER;
+        let mut ans = template.to_string();
+        if self.size != (None, None) {
+            ans = xml::map_child(&ans, "w:pgSz", order, |size| {
+                [("w:w", self.size.0), ("w:h", self.size.1)]
+                    .into_iter()
+                    .filter_map(|(k, v)| Some((k, v?)))
+                    .fold(size.to_string(), |s, (k, v)| xml::set_attr(&s, k, v))
+            });
+        }
+        if self.margins.iter().any(Option::is_some) {
+            ans = xml::map_child(&ans, "w:pgMar", order, |margin| {
+                ["w:top", "w:right", "w:bottom", "w:left"]
+                    .into_iter()
+                    .zip(self.margins)
+                    .filter_map(|(k, v)| Some((k, v?)))
+                    .fold(margin.to_string(), |m, (k, v)| xml::set_attr(&m, k, v))
+            });
+        }
+        if self.landscape {
+            ans = xml::map_child(&ans, "w:pgSz", order, |size| {
+                let w = xml::attr(size, "w:w").unwrap().to_string();
+                let h = xml::attr(size, "w:h").unwrap().to_string();
+                let size = xml::set_attr(size, "w:w", h);
+                let size = xml::set_attr(&size, "w:h", w);
+                xml::set_attr(&size, "w:orient", "landscape")
+            });
+            // 页边距随页面旋转
+            ans = xml::map_child(&ans, "w:pgMar", order, |margin| {
+                let get = |key| xml::attr(margin, key).unwrap().to_string();
+                let (top, right, bottom, left) =
+                    (get("w:top"), get("w:right"), get("w:bottom"), get("w:left"));
+                [
+                    ("w:top", left),
+                    ("w:right", top),
+                    ("w:bottom", right),
+                    ("w:left", bottom),
+                ]
+                .into_iter()
+                .fold(margin.to_string(), |m, (k, v)| xml::set_attr(&m, k, v))
+            });
+        }
+        if let Some(columns) = &self.columns {
+            ans = xml::set_child(&ans, &columns.build(), order);
+        }
+        if let Some(grid) = &self.grid {
+            ans = xml::set_child(&ans, &grid.build(&ans), order);
+        }
+        ans = if self.continuous {
+            xml::set_child(&ans, r#"<w:type w:val="continuous" />"#, order)
+        } else {
+            xml::remove_child(&ans, "w:type", order)
+        };
+        match &self.page_number {
+            Some((format, start)) => {
+                let start = start.map_or(String::new(), |s| format!(r#" w:start="{s}""#));
+                xml::set_child(
+                    &ans,
+                    &format!(r#"<w:pgNumType w:fmt="{format}"{start} />"#),
+                    order,
+                )
+            }
+            None => xml::remove_child(&ans, "w:pgNumType", order),
+        }
+    }
+}
+
+impl Columns {
+    fn new(num: usize) -> Self {
+        Self {
+            num,
+            space: None,
+            separator: false,
+        }
+    }
+
+    /// 从 `::: columns n=2 space=21 sep` 形式的块指令读取分栏设置，栏间距默认单位为磅
+    pub fn from_attributes(attrs: &Attributes) -> Self {
+        let num = attrs.get("n").map_or(2, |n| {
+            n.parse()
+                .unwrap_or_else(|_| panic!("Invalid column count: {n}"))
+        });
+        let space = attrs
+            .get("space")
+            .map(|space| match Length::parse(space, Unit::Points) {
+                Some(Length::Points(pt)) if pt >= 0. => twips(pt) as _,
+                _ => panic!("Invalid column space: {space}"),
+            });
+        Self {
+            num,
+            space,
+            separator: attrs.has("sep") || attrs.get("sep") == Some("true"),
+        }
+    }
+
+    fn build(&self) -> String {
+        let space = self
+            .space
+            .map_or(String::new(), |s| format!(r#" w:space="{s}""#));
+        let sep = if self.separator { r#" w:sep="1""# } else { "" };
+        format!(r#"<w:cols w:num="{}"{space}{sep} />"#, self.num)
+    }
+}
+
+impl Grid {
+    /// 按节的页面大小和页边距计算行距和字距
+    fn build(&self, section: &str) -> String {
+        let size = xml::find(section, "w:pgSz").map(|r| &section[r]).unwrap();
+        let margin = xml::find(section, "w:pgMar").map(|r| &section[r]).unwrap();
+        let get = |element, key| -> f64 { xml::attr(element, key).unwrap().parse().unwrap() };
+        let height = get(size, "w:h") - get(margin, "w:top") - get(margin, "w:bottom");
+        let width = get(size, "w:w") - get(margin, "w:left") - get(margin, "w:right");
+
+        let kind = self.kind.as_deref().unwrap_or(if self.chars.is_some() {
+            "linesAndChars"
+        } else {
+            "lines"
+        });
+        let mut ans = format!(r#"<w:docGrid w:type="{kind}""#);
+        if let Some(pitch) = self
+            .line_pitch
+            .or(self.lines.map(|n| (height / n as f64) as _))
+        {
+            ans += &format!(r#" w:linePitch="{pitch}""#)
+        }
+        if let Some(n) = self.chars {
+            // 字距是每字宽度与字号之差，单位为 1/4096 磅
+            let space = ((width / 20. / n as f64 - self.font_size) * 4096.) as isize;
+            ans += &format!(r#" w:charSpace="{space}""#)
+        }
+        ans + " />"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(settings: &str) -> Result<Section, String> {
+        parse(&settings.parse().unwrap(), Section::default()).map(|page| page.current)
+    }
+
+    #[test]
+    fn sets_paper_size_and_margins() {
+        let template = r#"<w:sectPr><w:pgSz w:w="11906" w:h="16838" /><w:pgMar w:top="1985" w:right="1701" w:bottom="1701" w:left="1701" w:header="851" /></w:sectPr>"#;
+        let section = page(
+            "[page]\nsize = \"A5\"\nheight = \"200mm\"\nmargin-top = \"37mm\"\nmargin-left = 72",
+        )
+        .unwrap();
+        let built = section.build(template);
+        assert!(
+            built.contains(r#"<w:pgSz w:w="8391" w:h="11339" />"#),
+            "{built}"
+        );
+        assert!(
+            built.contains(r#"<w:pgMar w:top="2098" w:right="1701" w:bottom="1701" w:left="1440" w:header="851" />"#),
+            "{built}"
+        );
+
+        let landscape = section.landscape(true).build(template);
+        assert!(
+            landscape.contains(r#"w:w="11339" w:h="8391" w:orient="landscape""#),
+            "{landscape}"
+        );
+        assert!(
+            landscape.contains(r#"w:top="1440" w:right="2098""#),
+            "{landscape}"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_page_setup() {
+        assert_eq!(
+            page("[page]\nsize = \"A6\"").err().unwrap(),
+            "Invalid page.size: A6, must be A3, A4, A5, B5 or letter"
+        );
+        assert_eq!(
+            page("[page]\nwidth = 0").err().unwrap(),
+            "page.width must be positive"
+        );
+        assert_eq!(
+            page("[page]\nmargin-left = \"-1mm\"").err().unwrap(),
+            "page.margin-left must be non-negative"
+        );
+        assert!(page("[page]\nmargin-right = 0").is_ok());
+    }
+}
@@ -1,32 +1,252 @@
-﻿use super::{
+use super::{
+    code::Diff,
     docx::{self, Docx, Run},
     paragraph::max_heading_depth,
+    unit::{half_points, hundredths, twips, Length, Unit},
+    xml,
 };
 use std::{
     collections::HashSet,
+    fmt::Write,
     sync::{
-        atomic::{AtomicBool, Ordering::Relaxed},
+        atomic::{AtomicBool, AtomicUsize, Ordering::Relaxed},
         LazyLock, Mutex,
     },
 };
 use toml::Value as Val;
 
-const HEADING_STYLE_ID: fn(u8) -> String = |depth| format!("Heading{depth}");
-const BASE_CODE_STYLE_ID: &str = "Code";
-const CODE_STYLE_ID: fn(&str) -> String = |lang| format!("Code-{}", lang.to_ascii_lowercase());
+pub const HEADING_STYLE_ID: fn(u8) -> String = |depth| format!("Heading{depth}");
+pub const HEADING_STYLE_NAME: fn(u8) -> String = |depth| format!("Heading {depth}");
+pub const BASE_CODE_STYLE_ID: &str = "Code";
+pub const CODE_STYLE_ID: fn(&str) -> String = |lang| format!("Code-{}", lang.to_ascii_lowercase());
+/// 突出显示的代码行的样式 ID 前缀，生成 xml 后去掉前缀并加上底色
+const HIGHLIGHTED_CODE_MARKER: &str = "md2docx-highlighted-";
+/// 段落排版的代码块默认的底色和边框
+pub const CODE_PARAGRAPH_DEFAULTS: &str = r#"[Code]
+background = "F6F8FA"
+border = { sides = "box", color = "D0D7DE" }"#;
+/// 差异代码块各行样式的默认底色
+pub const DIFF_DEFAULTS: &str = r#"[DiffInsert]
+background = "E6FFEC"
+[DiffDelete]
+background = "FFEBE9"
+[DiffHunk]
+background = "DDF4FF"
+color = "0550AE""#;
+const LINE_NUMBER_STYLE_ID: &str = "CodeLineNumber";
+/// 差异代码块各行的样式都基于此语言的代码样式
+const DIFF_LANG: &str = "diff";
+const DIFF_INSERT_STYLE_ID: &str = "DiffInsert";
+const DIFF_DELETE_STYLE_ID: &str = "DiffDelete";
+const DIFF_HUNK_STYLE_ID: &str = "DiffHunk";
 const INLINE_CODE_STYLE_ID: &str = "InlineCode";
-const BODY_TEXT_STYLE_ID: &str = "BodyText";
+pub const BODY_TEXT_STYLE_ID: &str = "BodyText";
 const IMAGE_STYLE_ID: &str = "Image";
 const TABLE_STYLE_ID: &str = "Table";
 const CAPTION_STYLE_ID: &str = "Caption";
-const QUOTE_STYLE_ID: &str = "Quote";
+pub const QUOTE_STYLE_ID: fn(usize) -> String = |depth| match depth {
+    1 => "Quote".into(),
+    _ => format!("Quote{depth}"),
+};
+/// 第一层引用默认的缩进和左边框
+pub const QUOTE_DEFAULTS: &str = r#"[Quote]
+left-indent = 2
+border = { sides = "left", color = "D0D7DE", width = 3 }
+color = "59636E""#;
+const HEADER_STYLE_ID: &str = "Header";
+const FOOTER_STYLE_ID: &str = "Footer";
+
+/// 样式表中的设置，与 `modify` 中处理的键一致
+pub const STYLE_KEYS: &[&str] = &[
+    "type",
+    "name",
+    "based-on",
+    "next",
+    "font",
+    "font-size",
+    "align",
+    "bold",
+    "italic",
+    "snap-to-grid",
+    "line-spacing",
+    "space-before",
+    "space-after",
+    "first-line-indent",
+    "hanging-indent",
+    "left-indent",
+    "right-indent",
+    "keep-with-next",
+    "keep-lines-together",
+    "widow-control",
+    "page-break-before",
+    "color",
+    "underline",
+    "highlight",
+    "shading",
+    "character-spacing",
+    "scale",
+    "position",
+    "caps",
+    "small-caps",
+    "emphasis-mark",
+    "background",
+    "border",
+    "layout",
+    "keep-together",
+    "syntax-theme",
+    "line-numbers",
+    "line-highlight",
+    "tab-width",
+    "wrap-at",
+    "wrap-marker",
+];
+/// 只在 `[Code]` 中读取的代码块设置
+const CODE_ONLY_KEYS: &[&str] = &[
+    "keep-together",
+    "syntax-theme",
+    "line-numbers",
+    "line-highlight",
+    "tab-width",
+    "wrap-at",
+    "wrap-marker",
+];
+/// 表形式的 `font` 中的设置
+pub const FONT_KEYS: &[&str] = &[
+    "east-asia",
+    "ascii",
+    "h-ansi",
+    "cs",
+    "east-asia-theme",
+    "ascii-theme",
+    "h-ansi-theme",
+    "cs-theme",
+    "hint",
+];
+/// 表形式的 `underline` 中的设置
+pub const UNDERLINE_KEYS: &[&str] = &["style", "color"];
+/// 表形式的 `border` 中的设置
+pub const BORDER_KEYS: &[&str] = &["sides", "color", "width"];
+/// 内置样式的 ID，标题、嵌套引用和各语言代码的样式另见 `is_builtin`
+pub const BUILTIN_STYLE_IDS: &[&str] = &[
+    BODY_TEXT_STYLE_ID,
+    INLINE_CODE_STYLE_ID,
+    BASE_CODE_STYLE_ID,
+    LINE_NUMBER_STYLE_ID,
+    DIFF_INSERT_STYLE_ID,
+    DIFF_DELETE_STYLE_ID,
+    DIFF_HUNK_STYLE_ID,
+    IMAGE_STYLE_ID,
+    TABLE_STYLE_ID,
+    CAPTION_STYLE_ID,
+    "Quote",
+    HEADER_STYLE_ID,
+    FOOTER_STYLE_ID,
+];
+/// 样式文件中不是样式的表
+pub const SETTING_TABLES: &[&str] = &["header", "footer", "page", "page-number"];
+
+pub const PARAGRAPH_PROPERTY_ORDER: &[&str] = &[
+    "w:pStyle",
+    "w:keepNext",
+    "w:keepLines",
+    "w:pageBreakBefore",
+    "w:framePr",
+    "w:widowControl",
+    "w:numPr",
+    "w:suppressLineNumbers",
+    "w:pBdr",
+    "w:shd",
+    "w:tabs",
+    "w:suppressAutoHyphens",
+    "w:kinsoku",
+    "w:wordWrap",
+    "w:overflowPunct",
+    "w:topLinePunct",
+    "w:autoSpaceDE",
+    "w:autoSpaceDN",
+    "w:bidi",
+    "w:adjustRightInd",
+    "w:snapToGrid",
+    "w:spacing",
+    "w:ind",
+    "w:contextualSpacing",
+    "w:mirrorIndents",
+    "w:suppressOverlap",
+    "w:jc",
+    "w:textDirection",
+    "w:textAlignment",
+    "w:textboxTightWrap",
+    "w:outlineLvl",
+    "w:divId",
+    "w:cnfStyle",
+    "w:rPr",
+    "w:sectPr",
+    "w:pPrChange",
+];
+
+pub const RUN_PROPERTY_ORDER: &[&str] = &[
+    "w:rStyle",
+    "w:rFonts",
+    "w:b",
+    "w:bCs",
+    "w:i",
+    "w:iCs",
+    "w:caps",
+    "w:smallCaps",
+    "w:strike",
+    "w:dstrike",
+    "w:outline",
+    "w:shadow",
+    "w:emboss",
+    "w:imprint",
+    "w:noProof",
+    "w:snapToGrid",
+    "w:vanish",
+    "w:webHidden",
+    "w:color",
+    "w:spacing",
+    "w:w",
+    "w:kern",
+    "w:position",
+    "w:sz",
+    "w:szCs",
+    "w:highlight",
+    "w:u",
+    "w:effect",
+    "w:bdr",
+    "w:shd",
+    "w:fitText",
+    "w:vertAlign",
+    "w:rtl",
+    "w:cs",
+    "w:em",
+    "w:lang",
+    "w:eastAsianLayout",
+    "w:specVanish",
+    "w:oMath",
+];
 
-static LANGUAGES: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Default::default());
+static LANGUAGES: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Default::default);
+static LINE_NUMBER_STYLE: AtomicBool = AtomicBool::new(false);
+static DIFF_STYLE: AtomicBool = AtomicBool::new(false);
 static INLINE_CODE_STYLE: AtomicBool = AtomicBool::new(false);
 static IMAGE_STYLE: AtomicBool = AtomicBool::new(false);
 static TABLE_STYLE: AtomicBool = AtomicBool::new(false);
 static CAPTION_STYLE: AtomicBool = AtomicBool::new(false);
-static QUOTE_STYLE: AtomicBool = AtomicBool::new(false);
+/// 用到的引用样式的最大嵌套层数
+static QUOTE_DEPTH: AtomicUsize = AtomicUsize::new(0);
+/// 引用是否排版为表格
+static QUOTE_TABLE_LAYOUT: AtomicBool = AtomicBool::new(false);
+static HEADER_STYLE: AtomicBool = AtomicBool::new(false);
+static FOOTER_STYLE: AtomicBool = AtomicBool::new(false);
+/// 样式文件中自定义的样式及其类型
+static CUSTOM_STYLES: LazyLock<Mutex<Vec<(String, docx::StyleType)>>> =
+    LazyLock::new(Default::default);
+/// 文档中用到的自定义样式，包括样式文件中未定义的
+static USED_CUSTOM_STYLES: LazyLock<Mutex<Vec<String>>> = LazyLock::new(Default::default);
+/// docx-rs 不支持的段落和字符属性，在生成 xml 后补充到样式中，依次为样式 ID、属性容器和属性元素
+static STYLE_PATCHES: LazyLock<Mutex<Vec<StylePatch>>> = LazyLock::new(Default::default);
+type StylePatch = (String, &'static str, String);
 
 pub fn heading_style(p: docx::Paragraph, depth: u8) -> docx::Paragraph {
     p.style(&HEADING_STYLE_ID(depth))
@@ -46,6 +266,31 @@ pub fn code_style(p: docx::Paragraph, lang: &str) -> docx::Paragraph {
     p.style(&CODE_STYLE_ID(lang))
 }
 
+/// 代码块的行号，两种排版都使用此字符样式
+pub fn line_number_style(run: Run) -> Run {
+    LINE_NUMBER_STYLE.store(true, Relaxed);
+    run.style(LINE_NUMBER_STYLE_ID)
+}
+
+/// 代码块中突出显示的行，底色加在已应用的代码或差异样式之上
+pub fn highlighted_code_style(p: docx::Paragraph) -> docx::Paragraph {
+    let id = p.property.style.as_ref().unwrap().val.clone();
+    p.style(&format!("{HIGHLIGHTED_CODE_MARKER}{id}"))
+}
+
+/// 差异代码块中的一行，样式基于 `diff` 代码块的样式
+pub fn diff_style(p: docx::Paragraph, lang: &str, diff: Diff) -> docx::Paragraph {
+    let id = match diff {
+        Diff::Insert => DIFF_INSERT_STYLE_ID,
+        Diff::Delete => DIFF_DELETE_STYLE_ID,
+        Diff::Hunk => DIFF_HUNK_STYLE_ID,
+        Diff::Context => return code_style(p, lang),
+    };
+    LANGUAGES.lock().unwrap().insert(DIFF_LANG.into());
+    DIFF_STYLE.store(true, Relaxed);
+    p.style(id)
+}
+
 pub fn image_style(p: docx::Paragraph) -> docx::Paragraph {
     IMAGE_STYLE.store(true, Relaxed);
     p.style(IMAGE_STYLE_ID)
@@ -61,11 +306,174 @@ pub fn caption_style(p: docx::Paragraph) -> docx::Paragraph {
     p.style(CAPTION_STYLE_ID)
 }
 
-// TODO
-// pub fn quote_style(p: docx::Paragraph) -> docx::Paragraph {
-//     QUOTE_STYLE.store(true, Relaxed);
-//     p.style(QUOTE_STYLE_ID)
-// }
+pub fn header_style(p: docx::Paragraph) -> docx::Paragraph {
+    HEADER_STYLE.store(true, Relaxed);
+    p.style(HEADER_STYLE_ID)
+}
+
+pub fn footer_style(p: docx::Paragraph) -> docx::Paragraph {
+    FOOTER_STYLE.store(true, Relaxed);
+    p.style(FOOTER_STYLE_ID)
+}
+
+/// 对 `:::` 块中的段落应用自定义样式，字符样式只应用到尚未设置样式的文字上
+///
+/// 样式文件中未定义的样式作为段落样式应用。
+pub fn custom_style(mut p: docx::Paragraph, id: &str) -> docx::Paragraph {
+    use_custom_style(id);
+    match style_type(id) {
+        Some(docx::StyleType::Paragraph) | None => p.style(id),
+        Some(docx::StyleType::Character) => {
+            for child in &mut p.children {
+                if let docx::ParagraphChild::Run(run) = child {
+                    if run.run_property.style.is_none() {
+                        run.run_property = run.run_property.clone().style(id);
+                    }
+                }
+            }
+            p
+        }
+        _ => p,
+    }
+}
+
+/// 对 `:::` 块中的表格应用自定义的表格样式
+pub fn custom_table_style(t: docx::Table, id: &str) -> docx::Table {
+    use_custom_style(id);
+    match style_type(id) {
+        Some(docx::StyleType::Table) => t.style(id),
+        _ => t,
+    }
+}
+
+/// 记录文档中用到的自定义样式，内置样式另行记录
+fn use_custom_style(id: &str) {
+    let mut used = USED_CUSTOM_STYLES.lock().unwrap();
+    if !is_builtin(id) && !used.iter().any(|u| u == id) {
+        used.push(id.into());
+    }
+}
+
+/// 是否是样式文件中自定义的样式
+pub fn is_custom_style(id: &str) -> bool {
+    CUSTOM_STYLES.lock().unwrap().iter().any(|(i, _)| i == id)
+}
+
+/// 样式的类型，样式文件中未定义的自定义样式为 `None`
+fn style_type(id: &str) -> Option<docx::StyleType> {
+    match CUSTOM_STYLES.lock().unwrap().iter().find(|(i, _)| i == id) {
+        Some(&(_, style_type)) => Some(style_type),
+        None if id == INLINE_CODE_STYLE_ID || id == LINE_NUMBER_STYLE_ID => {
+            Some(docx::StyleType::Character)
+        }
+        None if id == TABLE_STYLE_ID => Some(docx::StyleType::Table),
+        None if is_builtin(id) => Some(docx::StyleType::Paragraph),
+        None => None,
+    }
+}
+
+/// 内置的样式 ID，以及样式文件中其他用途的表
+pub fn is_builtin(id: &str) -> bool {
+    BUILTIN_STYLE_IDS.contains(&id)
+        || id
+            .strip_prefix("Heading")
+            .is_some_and(|depth| depth.parse::<u8>().is_ok())
+        || id
+            .strip_prefix("Quote")
+            .is_some_and(|depth| depth.parse::<u8>().is_ok())
+        || id.starts_with("Code-")
+        || SETTING_TABLES.contains(&id)
+}
+
+/// 读取样式文件中的自定义样式，即内置样式以外的表
+pub fn init(settings: &toml::Table) {
+    if let Some(Val::Table(quote)) = settings.get("Quote") {
+        if let Some(val) = quote.get("layout") {
+            let paragraphs = layout(val).unwrap_or_else(|e| panic!("Invalid style Quote: {e}"));
+            QUOTE_TABLE_LAYOUT.store(!paragraphs, Relaxed);
+        }
+    }
+
+    let mut custom = Vec::new();
+    for (id, val) in settings {
+        let Val::Table(style) = val else {
+            continue;
+        };
+        if is_builtin(id) {
+            continue;
+        }
+        let style_type = match style.get("type") {
+            Some(val) => custom_type(val).unwrap_or_else(|e| panic!("Invalid style {id}: {e}")),
+            None => docx::StyleType::Paragraph,
+        };
+        custom.push((id.clone(), style_type));
+    }
+
+    // 检查自定义样式之间的 based-on 是否成环
+    let based_on = |id: &str| match settings.get(id) {
+        Some(Val::Table(style)) => match style.get("based-on") {
+            Some(Val::String(base)) => Some(base.as_str()),
+            _ => None,
+        },
+        _ => None,
+    };
+    for (id, _) in &custom {
+        let mut chain = vec![id.as_str()];
+        while let Some(base) = based_on(chain.last().unwrap()) {
+            if chain.contains(&base) {
+                panic!(
+                    "Style {id} has a based-on cycle: {} -> {base}",
+                    chain.join(" -> ")
+                );
+            }
+            if !is_builtin(base) && !custom.iter().any(|(i, _)| i == base) {
+                eprintln!("Style {id} is based on an undefined style: {base}");
+            }
+            chain.push(base);
+        }
+    }
+    *CUSTOM_STYLES.lock().unwrap() = custom;
+}
+
+/// 第 `depth` 层引用中的段落，从 1 开始；只替换正文样式，图片和题注保持原样
+pub fn quote_style(p: docx::Paragraph, depth: usize) -> docx::Paragraph {
+    match &p.property.style {
+        Some(style) if style.val == BODY_TEXT_STYLE_ID => {
+            QUOTE_DEPTH.fetch_max(depth, Relaxed);
+            p.style(&QUOTE_STYLE_ID(depth))
+        }
+        _ => p,
+    }
+}
+
+pub fn quote_table_layout() -> bool {
+    QUOTE_TABLE_LAYOUT.load(Relaxed)
+}
+
+/// `table` 或 `paragraphs`，返回是否排版为段落
+pub fn layout(val: &Val) -> Result<bool, String> {
+    match val.as_str() {
+        Some("table") => Ok(false),
+        Some("paragraphs") => Ok(true),
+        _ => Err("layout must be table or paragraphs".into()),
+    }
+}
+
+/// 自定义样式的类型
+pub fn custom_type(val: &Val) -> Result<docx::StyleType, String> {
+    use docx::StyleType;
+    match val {
+        Val::String(t) => match t.as_str() {
+            "paragraph" => Ok(StyleType::Paragraph),
+            "character" => Ok(StyleType::Character),
+            "table" => Ok(StyleType::Table),
+            _ => Err(format!(
+                "Invalid style type: {t}, must be paragraph, character or table"
+            )),
+        },
+        _ => Err("type must be a string".into()),
+    }
+}
 
 pub fn add_style(mut docx: Docx, settings: toml::Table) -> Docx {
     use docx::{
@@ -81,7 +489,7 @@ pub fn add_style(mut docx: Docx, settings: toml::Table) -> Docx {
         docx = docx.add_style(modify(
             Style::new(HEADING_STYLE_ID(i as _), StyleType::Paragraph)
                 .based_on(BODY_TEXT_STYLE_ID)
-                .name(format!("Heading {i}")),
+                .name(HEADING_STYLE_NAME(i as _)),
             &settings,
         ));
     }
@@ -117,23 +525,82 @@ pub fn add_style(mut docx: Docx, settings: toml::Table) -> Docx {
             &settings,
         ));
     }
-    if QUOTE_STYLE.load(Relaxed) {
+    for depth in 1..=QUOTE_DEPTH.load(Relaxed) {
+        // 每层引用多缩进与第一层相同的距离，左边框由上一层继承
+        let defaults = match depth {
+            1 => QUOTE_DEFAULTS.into(),
+            _ => {
+                let indent = match quote_indent(&settings) {
+                    Length::Points(pt) => format!(r#""{}pt""#, pt * depth as f64),
+                    Length::Chars(chars) => (chars * depth as f64).to_string(),
+                    // 第一层引用的设置中已检查
+                    Length::Lines(_) => unreachable!(),
+                };
+                format!("[Quote{depth}]\nleft-indent = {indent}")
+            }
+        };
+        let style = Style::new(QUOTE_STYLE_ID(depth), StyleType::Paragraph)
+            .based_on(match depth {
+                1 => BODY_TEXT_STYLE_ID.into(),
+                _ => QUOTE_STYLE_ID(depth - 1),
+            })
+            .name(match depth {
+                1 => "Quote".into(),
+                _ => format!("Quote {depth}"),
+            });
+        docx = docx.add_style(modify(modify(style, &defaults.parse().unwrap()), &settings));
+    }
+    if HEADER_STYLE.load(Relaxed) {
+        docx = docx.add_style(modify(
+            Style::new(HEADER_STYLE_ID, StyleType::Paragraph)
+                .based_on(BODY_TEXT_STYLE_ID)
+                .align(Center)
+                .name("header"),
+            &settings,
+        ));
+    }
+    if FOOTER_STYLE.load(Relaxed) {
         docx = docx.add_style(modify(
-            Style::new(BODY_TEXT_STYLE_ID, StyleType::Paragraph)
+            Style::new(FOOTER_STYLE_ID, StyleType::Paragraph)
                 .based_on(BODY_TEXT_STYLE_ID)
                 .align(Center)
-                .name(QUOTE_STYLE_ID),
+                .name("footer"),
+            &settings,
+        ));
+    }
+    for (id, style_type) in &*CUSTOM_STYLES.lock().unwrap() {
+        let style = Style::new(id, *style_type).name(id);
+        docx = docx.add_style(modify(
+            match style_type {
+                StyleType::Paragraph => style.based_on(BODY_TEXT_STYLE_ID),
+                _ => style,
+            },
             &settings,
         ));
     }
+    for id in &*USED_CUSTOM_STYLES.lock().unwrap() {
+        if !is_custom_style(id) {
+            eprintln!("Undefined style: {id}");
+        }
+    }
     {
         let languages = LANGUAGES.lock().unwrap();
         if !languages.is_empty() {
+            let mut style = Style::new(BASE_CODE_STYLE_ID, StyleType::Paragraph)
+                .based_on(BODY_TEXT_STYLE_ID)
+                .align(Left)
+                .name("Code");
+            // 样式文件中的设置覆盖默认的底色和边框
+            if super::code::paragraph_layout() {
+                style = modify(style, &CODE_PARAGRAPH_DEFAULTS.parse().unwrap());
+            }
+            docx = docx.add_style(modify(style, &settings));
+        }
+        if LINE_NUMBER_STYLE.load(Relaxed) {
             docx = docx.add_style(modify(
-                Style::new(BASE_CODE_STYLE_ID, StyleType::Paragraph)
-                    .based_on(BODY_TEXT_STYLE_ID)
-                    .align(Left)
-                    .name("Code"),
+                Style::new(LINE_NUMBER_STYLE_ID, StyleType::Character)
+                    .color("808080")
+                    .name("Code Line Number"),
                 &settings,
             ));
         }
@@ -145,12 +612,105 @@ pub fn add_style(mut docx: Docx, settings: toml::Table) -> Docx {
                 &settings,
             ));
         }
+        if DIFF_STYLE.load(Relaxed) {
+            let defaults = DIFF_DEFAULTS.parse().unwrap();
+            for (id, name) in [
+                (DIFF_INSERT_STYLE_ID, "Diff Insert"),
+                (DIFF_DELETE_STYLE_ID, "Diff Delete"),
+                (DIFF_HUNK_STYLE_ID, "Diff Hunk"),
+            ] {
+                let style = Style::new(id, StyleType::Paragraph)
+                    .based_on(CODE_STYLE_ID(DIFF_LANG))
+                    .name(name);
+                docx = docx.add_style(modify(modify(style, &defaults), &settings));
+            }
+        }
     }
 
     docx
 }
 
-fn modify(mut style: docx::Style, settings: &toml::Table) -> docx::Style {
+/// 第一层引用的左缩进，样式文件中未设置时使用默认值
+fn quote_indent(settings: &toml::Table) -> Length {
+    let defaults = QUOTE_DEFAULTS.parse::<toml::Table>().unwrap();
+    let get = |settings: &toml::Table| settings.get("Quote")?.get("left-indent").cloned();
+    let val = get(settings).or_else(|| get(&defaults)).unwrap();
+    Length::from_val(&val, "left-indent", Unit::Chars)
+        .unwrap_or_else(|e| panic!("Invalid style Quote: {e}"))
+}
+
+/// 已用到的样式的 ID，内置样式的顺序与 `add_style` 一致，最后是用到的自定义样式
+pub fn used_style_ids() -> Vec<String> {
+    let mut ids = vec![BODY_TEXT_STYLE_ID.to_string()];
+    ids.extend((1..=max_heading_depth()).map(|i| HEADING_STYLE_ID(i as _)));
+    for (used, id) in [
+        (&INLINE_CODE_STYLE, INLINE_CODE_STYLE_ID),
+        (&IMAGE_STYLE, IMAGE_STYLE_ID),
+        (&TABLE_STYLE, TABLE_STYLE_ID),
+        (&CAPTION_STYLE, CAPTION_STYLE_ID),
+    ] {
+        if used.load(Relaxed) {
+            ids.push(id.into());
+        }
+    }
+    ids.extend((1..=QUOTE_DEPTH.load(Relaxed)).map(QUOTE_STYLE_ID));
+    for (used, id) in [
+        (&HEADER_STYLE, HEADER_STYLE_ID),
+        (&FOOTER_STYLE, FOOTER_STYLE_ID),
+    ] {
+        if used.load(Relaxed) {
+            ids.push(id.into());
+        }
+    }
+    let languages = LANGUAGES.lock().unwrap();
+    if !languages.is_empty() {
+        ids.push(BASE_CODE_STYLE_ID.into());
+    }
+    if LINE_NUMBER_STYLE.load(Relaxed) {
+        ids.push(LINE_NUMBER_STYLE_ID.into());
+    }
+    let mut code = languages
+        .iter()
+        .map(|lang| CODE_STYLE_ID(lang))
+        .collect::<Vec<_>>();
+    code.sort();
+    ids.extend(code);
+    if DIFF_STYLE.load(Relaxed) {
+        ids.extend(
+            [
+                DIFF_INSERT_STYLE_ID,
+                DIFF_DELETE_STYLE_ID,
+                DIFF_HUNK_STYLE_ID,
+            ]
+            .map(String::from),
+        );
+    }
+    ids.extend(USED_CUSTOM_STYLES.lock().unwrap().iter().cloned());
+    ids
+}
+
+/// 一项样式设置解析后的结果
+enum Setting {
+    Name(String),
+    BasedOn(String),
+    Next(String),
+    Fonts(docx::RunFonts),
+    /// 字号，单位为半磅
+    Size(usize),
+    Align(docx::AlignmentType),
+    TableAlign(docx::TableAlignmentType),
+    Bold(bool),
+    Italic(bool),
+    Color(String),
+    /// docx-rs 不支持的段落属性元素
+    Paragraph(String),
+    /// docx-rs 不支持的字符属性元素
+    Run(String),
+    /// 样式类型，以及在转换前读取的设置，这里只检查
+    Checked,
+}
+
+pub fn modify(mut style: docx::Style, settings: &toml::Table) -> docx::Style {
     let Some(value) = settings.get(&style.style_id) else {
         return style;
     };
@@ -161,105 +721,719 @@ fn modify(mut style: docx::Style, settings: &toml::Table) -> docx::Style {
         )
     };
     for (key, val) in settings {
-        match key.as_str() {
-            "font" => style = set_fonts(style, val),
-            "font-size" => style = set_font_size(style, val),
-            "align" => style = set_alignment(style, val),
-            "bold" => style = set_bold(style, val),
-            "italic" => style = set_italic(style, val),
-            key => eprintln!("Unknown style setting: {}", key),
+        if !STYLE_KEYS.contains(&key.as_str()) {
+            eprintln!("Unknown style setting: {key}");
+            continue;
         }
+        if let (Some(keys), Val::Table(sub)) = (sub_keys(key), val) {
+            for sub_key in sub.keys().filter(|k| !keys.contains(&k.as_str())) {
+                eprintln!("Unknown {key} setting: {sub_key}");
+            }
+        }
+        let setting = setting(style.style_type, key, val)
+            .unwrap_or_else(|e| panic!("Invalid style {}: {e}", style.style_id));
+        if let Err(e) = applies(&style.style_id, style.style_type, key, &setting) {
+            eprintln!("Ignored style setting: {e}");
+            continue;
+        }
+        style = match setting {
+            Setting::Name(name) => style.name(name),
+            Setting::BasedOn(base) => style.based_on(base),
+            Setting::Next(next) => style.next(next),
+            Setting::Fonts(fonts) => style.fonts(fonts),
+            Setting::Size(size) => style.size(size),
+            Setting::Align(align) => style.align(align),
+            Setting::TableAlign(align) => style.table_align(align),
+            Setting::Bold(true) => style.bold(),
+            Setting::Bold(false) => {
+                style.run_property = style.run_property.disable_bold();
+                style
+            }
+            Setting::Italic(true) => style.italic(),
+            Setting::Italic(false) => {
+                style.run_property = style.run_property.disable_italic();
+                style
+            }
+            Setting::Color(color) => style.color(color),
+            Setting::Paragraph(element) => {
+                patch(&style.style_id, "w:pPr", element);
+                style
+            }
+            Setting::Run(element) => {
+                patch(&style.style_id, "w:rPr", element);
+                style
+            }
+            Setting::Checked => style,
+        };
     }
     style
 }
 
-fn set_fonts(style: docx::Style, val: &Val) -> docx::Style {
+/// 检查样式 `id` 的一项设置，不修改任何状态，`key` 须是 `STYLE_KEYS` 之一
+pub fn check(id: &str, style_type: docx::StyleType, key: &str, val: &Val) -> Result<(), String> {
+    applies(id, style_type, key, &setting(style_type, key, val)?)
+}
+
+/// 设置是否对样式 `id` 有效：代码块设置只在 `[Code]` 中读取，字符样式没有段落属性
+fn applies(
+    id: &str,
+    style_type: docx::StyleType,
+    key: &str,
+    setting: &Setting,
+) -> Result<(), String> {
+    let quote = QUOTE_STYLE_ID(1);
+    match key {
+        "layout" if id != BASE_CODE_STYLE_ID && id != quote => Err(format!(
+            "`layout` can only be set on `{BASE_CODE_STYLE_ID}` and `{quote}`, not `{id}`"
+        )),
+        _ if CODE_ONLY_KEYS.contains(&key) && id != BASE_CODE_STYLE_ID => Err(format!(
+            "`{key}` can only be set on `{BASE_CODE_STYLE_ID}`, not `{id}`"
+        )),
+        _ if matches!(setting, Setting::Paragraph(_))
+            && matches!(style_type, docx::StyleType::Character) =>
+        {
+            Err(format!(
+                "`{key}` is a paragraph setting and can't be set on character style `{id}`"
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 样式文件中直接设置的一项段落或字符属性
+pub enum Property {
+    /// docx-rs 生成的属性，按元素名从转换结果的样式中取出
+    Built(&'static str, &'static str),
+    /// 样式补丁中的属性元素，只含设置过的属性
+    Patch(&'static str, String),
+}
+
+/// 样式文件中直接为 `id` 设置的段落和字符属性，用于覆盖模板中的同名样式
+///
+/// 需要在 `init` 之后调用，以便确定自定义样式的类型。
+pub fn set_properties(id: &str, settings: &toml::Table) -> Vec<Property> {
+    use Property::{Built, Patch};
+
+    let Some(Val::Table(style)) = settings.get(id) else {
+        return vec![];
+    };
+    if SETTING_TABLES.contains(&id) {
+        return vec![];
+    }
+    let style_type = style_type(id).unwrap_or(docx::StyleType::Paragraph);
+    let mut ans = Vec::new();
+    for (key, val) in style {
+        let Ok(setting) = setting(style_type, key, val) else {
+            continue;
+        };
+        if applies(id, style_type, key, &setting).is_err() {
+            continue;
+        }
+        match setting {
+            Setting::Fonts(_) => ans.push(Built("w:rPr", "w:rFonts")),
+            Setting::Size(_) => ans.extend([Built("w:rPr", "w:sz"), Built("w:rPr", "w:szCs")]),
+            Setting::Align(_) => ans.push(Built("w:pPr", "w:jc")),
+            Setting::Bold(_) => ans.extend([Built("w:rPr", "w:b"), Built("w:rPr", "w:bCs")]),
+            Setting::Italic(_) => ans.extend([Built("w:rPr", "w:i"), Built("w:rPr", "w:iCs")]),
+            Setting::Color(_) => ans.push(Built("w:rPr", "w:color")),
+            Setting::Paragraph(element) => ans.push(Patch("w:pPr", element)),
+            Setting::Run(element) => ans.push(Patch("w:rPr", element)),
+            _ => {}
+        }
+    }
+    ans
+}
+
+/// 表形式的设置中可用的键
+pub fn sub_keys(key: &str) -> Option<&'static [&'static str]> {
+    match key {
+        "font" => Some(FONT_KEYS),
+        "underline" => Some(UNDERLINE_KEYS),
+        "border" => Some(BORDER_KEYS),
+        _ => None,
+    }
+}
+
+fn setting(style_type: docx::StyleType, key: &str, val: &Val) -> Result<Setting, String> {
+    use super::code;
+
+    Ok(match key {
+        "type" => {
+            custom_type(val)?;
+            Setting::Checked
+        }
+        "name" => Setting::Name(string(val, key)?.into()),
+        "based-on" => Setting::BasedOn(string(val, key)?.into()),
+        "next" => Setting::Next(string(val, key)?.into()),
+        "font" => Setting::Fonts(fonts(val)?),
+        "font-size" => Setting::Size(half_points(font_size(val)?) as _),
+        "align" => alignment(style_type, val)?,
+        "bold" => Setting::Bold(code::flag(val, key)?),
+        "italic" => Setting::Italic(code::flag(val, key)?),
+        "snap-to-grid" => Setting::Paragraph(toggle("w:snapToGrid", key, val)?),
+        "line-spacing" => Setting::Paragraph(line_spacing(val)?),
+        "space-before" => Setting::Paragraph(space("before", key, val)?),
+        "space-after" => Setting::Paragraph(space("after", key, val)?),
+        "first-line-indent" => Setting::Paragraph(indent("firstLine", key, val)?),
+        "hanging-indent" => Setting::Paragraph(indent("hanging", key, val)?),
+        "left-indent" => Setting::Paragraph(indent("left", key, val)?),
+        "right-indent" => Setting::Paragraph(indent("right", key, val)?),
+        "keep-with-next" => Setting::Paragraph(toggle("w:keepNext", key, val)?),
+        "keep-lines-together" => Setting::Paragraph(toggle("w:keepLines", key, val)?),
+        "widow-control" => Setting::Paragraph(toggle("w:widowControl", key, val)?),
+        "page-break-before" => Setting::Paragraph(toggle("w:pageBreakBefore", key, val)?),
+        "color" => Setting::Color(color(val, key)?),
+        "underline" => Setting::Run(underline(val)?),
+        "highlight" => Setting::Run(highlight(val)?),
+        "shading" => Setting::Run(shading(val, key)?),
+        "character-spacing" => Setting::Run(format!(
+            r#"<w:spacing w:val="{}" />"#,
+            twips(Length::points(val, key)?)
+        )),
+        "scale" => Setting::Run(format!(r#"<w:w w:val="{}" />"#, number(val, key)? as usize)),
+        "position" => Setting::Run(format!(
+            r#"<w:position w:val="{}" />"#,
+            half_points(Length::points(val, key)?)
+        )),
+        "caps" => Setting::Run(toggle("w:caps", key, val)?),
+        "small-caps" => Setting::Run(toggle("w:smallCaps", key, val)?),
+        "emphasis-mark" => Setting::Run(emphasis_mark(val)?),
+        "background" => Setting::Paragraph(shading(val, key)?),
+        "border" => Setting::Paragraph(border(val)?),
+        // 代码块和引用的排版、高亮主题、行号、制表符和折行设置在转换前读取，这里只检查
+        "layout" => {
+            layout(val)?;
+            Setting::Checked
+        }
+        "syntax-theme" => {
+            code::theme(val)?;
+            Setting::Checked
+        }
+        "line-numbers" | "keep-together" => {
+            code::flag(val, key)?;
+            Setting::Checked
+        }
+        "line-highlight" => {
+            color(val, key)?;
+            Setting::Checked
+        }
+        "tab-width" | "wrap-at" => {
+            code::columns(val, key)?;
+            Setting::Checked
+        }
+        "wrap-marker" => {
+            code::wrap_marker(val)?;
+            Setting::Checked
+        }
+        key => return Err(format!("Unknown style setting: {key}")),
+    })
+}
+
+fn fonts(val: &Val) -> Result<docx::RunFonts, String> {
     use docx::RunFonts;
+    let both =
+        |asia: &str, ascii: &str| RunFonts::new().east_asia(asia).ascii(ascii).hi_ansi(ascii);
     match val {
         Val::Array(arr) => match &arr[..] {
-            [Val::String(font)] => style.fonts(RunFonts::new().east_asia(font).ascii(font)),
-            [Val::String(asia), Val::String(ascii)] => {
-                style.fonts(RunFonts::new().east_asia(asia).ascii(ascii))
-            }
-            [..] => panic!("Font array must be [font] or [asia, ascii]"),
+            [Val::String(font)] => Ok(both(font, font)),
+            [Val::String(asia), Val::String(ascii)] => Ok(both(asia, ascii)),
+            [..] => Err("Font array must be [font] or [asia, ascii]".into()),
         },
-        Val::String(font) => style.fonts(RunFonts::new().east_asia(font).ascii(font)),
-        _ => panic!("Font must be an array or a string"),
+        Val::String(font) => Ok(both(font, font)),
+        Val::Table(fonts) => font_slots(fonts),
+        _ => Err("Font must be an array, a string or a table".into()),
+    }
+}
+
+/// 分别设置各字体槽位，如 `{ east-asia = "宋体", ascii = "Times New Roman" }`
+///
+/// 每个槽位可用 `*-theme` 引用主题字体，值为 `major` 或 `minor`，也可以写完整的名称如 `minor-h-ansi`。
+/// 未设置 `h-ansi` 时使用 `ascii` 的设置。
+fn font_slots(settings: &toml::Table) -> Result<docx::RunFonts, String> {
+    let mut fonts = docx::RunFonts::new();
+    let get = |key: &str| match settings.get(key) {
+        Some(Val::String(font)) => Ok(Some(font.as_str())),
+        Some(_) => Err(format!("font.{key} must be a string")),
+        None => Ok(None),
+    };
+    let theme = |key: &str, slot: &str| -> Result<Option<String>, String> {
+        let Some(theme) = get(key)? else {
+            return Ok(None);
+        };
+        Ok(Some(match theme {
+            "major" | "minor" => format!("{theme}{slot}"),
+            _ => match theme.split_once('-') {
+                Some((kind @ ("major" | "minor"), name)) => match name {
+                    "ascii" => format!("{kind}Ascii"),
+                    "h-ansi" => format!("{kind}HAnsi"),
+                    "east-asia" => format!("{kind}EastAsia"),
+                    "bidi" => format!("{kind}Bidi"),
+                    _ => return Err(format!("Invalid theme font: {theme}")),
+                },
+                _ => return Err(format!("Invalid theme font: {theme}")),
+            },
+        }))
+    };
+
+    if let Some(font) = get("east-asia")? {
+        fonts = fonts.east_asia(font);
+    }
+    if let Some(font) = get("ascii")? {
+        fonts = fonts.ascii(font);
+    }
+    if let Some(font) = get("h-ansi")?.or(get("ascii")?) {
+        fonts = fonts.hi_ansi(font);
     }
+    if let Some(font) = get("cs")? {
+        fonts = fonts.cs(font);
+    }
+    if let Some(theme) = theme("east-asia-theme", "EastAsia")? {
+        fonts = fonts.east_asia_theme(theme);
+    }
+    if let Some(theme) = theme("ascii-theme", "Ascii")? {
+        fonts = fonts.ascii_theme(theme);
+    }
+    let h_ansi = match theme("h-ansi-theme", "HAnsi")? {
+        Some(theme) => Some(theme),
+        None => theme("ascii-theme", "HAnsi")?.map(|t| t.replace("Ascii", "HAnsi")),
+    };
+    if let Some(theme) = h_ansi {
+        fonts = fonts.hi_ansi_theme(theme);
+    }
+    if let Some(theme) = theme("cs-theme", "Bidi")? {
+        fonts = fonts.cs_theme(theme);
+    }
+    if let Some(hint) = get("hint")? {
+        fonts = fonts.hint(match hint {
+            "east-asia" => "eastAsia",
+            "default" | "cs" => hint,
+            _ => {
+                return Err(format!(
+                    "Invalid font hint: {hint}, must be default, east-asia or cs"
+                ))
+            }
+        });
+    }
+    Ok(fonts)
 }
 
-fn set_font_size(style: docx::Style, val: &Val) -> docx::Style {
-    #[inline(always)]
-    fn map_size(size: f64) -> usize {
-        (size * 2.) as _
+/// 解析字号，单位为磅
+pub fn font_size(val: &Val) -> Result<f64, String> {
+    let named = match val {
+        Val::String(size) => match size.as_str() {
+            "初号" => Some(42.),
+            "小初" => Some(36.),
+            "一号" => Some(26.),
+            "小一" => Some(24.),
+            "二号" => Some(22.),
+            "小二" => Some(18.),
+            "三号" => Some(16.),
+            "小三" => Some(15.),
+            "四号" => Some(14.),
+            "小四" => Some(12.),
+            "五号" => Some(10.5),
+            "小五" => Some(9.),
+            "六号" => Some(7.5),
+            "七号" => Some(5.5),
+            "八号" => Some(5.),
+            _ => None,
+        },
+        _ => None,
+    };
+    match named {
+        Some(pt) => Ok(pt),
+        None => Length::points(val, "font-size"),
     }
+}
 
-    match val {
-        Val::String(size) => style.size(map_size(match size.as_str() {
-            "初号" => 42.,
-            "小初" => 36.,
-            "一号" => 26.,
-            "小一" => 24.,
-            "二号" => 22.,
-            "小二" => 18.,
-            "三号" => 16.,
-            "小三" => 15.,
-            "四号" => 14.,
-            "小四" => 12.,
-            "五号" => 10.5,
-            "小五" => 9.,
-            "六号" => 7.5,
-            "七号" => 5.5,
-            "八号" => 5.,
-            val => val
-                .parse()
-                .unwrap_or_else(|_| panic!("Invalid font size: {val}")),
-        })),
-        &Val::Integer(size) => style.size(map_size(size as _)),
-        &Val::Float(size) => style.size(map_size(size)),
-        _ => panic!("Font-size must be a string or a number"),
-    }
-}
-
-fn set_alignment(style: docx::Style, val: &Val) -> docx::Style {
+fn alignment(style_type: docx::StyleType, val: &Val) -> Result<Setting, String> {
     use docx::{AlignmentType as P, StyleType, TableAlignmentType as T};
-    match val {
-        Val::String(align) => match align.as_str() {
-            "center" | "c" => match style.style_type {
-                StyleType::Paragraph => style.align(P::Center),
-                StyleType::Table => style.table_align(T::Center),
-                _ => panic!("alignment only supported for paragraphs and tables"),
-            },
-            "left" | "l" => match style.style_type {
-                StyleType::Paragraph => style.align(P::Left),
-                StyleType::Table => style.table_align(T::Left),
-                _ => panic!("alignment only supported for paragraphs and tables"),
-            },
-            "right" | "r" => match style.style_type {
-                StyleType::Paragraph => style.align(P::Right),
-                StyleType::Table => style.table_align(T::Right),
-                _ => panic!("alignment only supported for paragraphs and tables"),
+    let Val::String(align) = val else {
+        return Err("align must be a string".into());
+    };
+    let (p, t) = match align.as_str() {
+        "center" | "c" => (P::Center, T::Center),
+        "left" | "l" => (P::Left, T::Left),
+        "right" | "r" => (P::Right, T::Right),
+        _ => return Err(format!("Invalid alignment: {align}")),
+    };
+    match style_type {
+        StyleType::Paragraph => Ok(Setting::Align(p)),
+        StyleType::Table => Ok(Setting::TableAlign(t)),
+        _ => Err("alignment only supported for paragraphs and tables".into()),
+    }
+}
+
+/// 开关属性，如 `keep-with-next = true`
+fn toggle(element: &str, key: &str, val: &Val) -> Result<String, String> {
+    let on = super::code::flag(val, key)?;
+    Ok(format!(
+        r#"<{element} w:val="{}" />"#,
+        if on { 1 } else { 0 }
+    ))
+}
+
+/// 颜色，`auto` 或十六进制 RGB 值，可以带 `#` 前缀
+pub fn color(val: &Val, key: &str) -> Result<String, String> {
+    let Val::String(color) = val else {
+        return Err(format!("{key} must be a string"));
+    };
+    let color = color.strip_prefix('#').unwrap_or(color);
+    if color != "auto" && !(color.len() == 6 && color.chars().all(|c| c.is_ascii_hexdigit())) {
+        return Err(format!(
+            "Invalid {key}: {color}, must be auto or a hex RGB value"
+        ));
+    }
+    Ok(color.to_ascii_uppercase().replace("AUTO", "auto"))
+}
+
+/// 底纹，字符的 `shading` 和段落的 `background` 共用
+fn shading(val: &Val, key: &str) -> Result<String, String> {
+    Ok(format!(
+        r#"<w:shd w:val="clear" w:color="auto" w:fill="{}" />"#,
+        color(val, key)?
+    ))
+}
+
+/// 下划线：布尔值、线型，或 `{ style = 线型, color = 颜色 }`
+fn underline(val: &Val) -> Result<String, String> {
+    let (kind, color) = match val {
+        Val::Boolean(true) => ("single", None),
+        Val::Boolean(false) => ("none", None),
+        Val::String(kind) => (kind.as_str(), None),
+        Val::Table(underline) => {
+            let kind = match underline.get("style") {
+                Some(Val::String(kind)) => kind.as_str(),
+                Some(_) => return Err("underline.style must be a string".into()),
+                None => "single",
+            };
+            let color = match underline.get("color") {
+                Some(c) => Some(color(c, "underline.color")?),
+                None => None,
+            };
+            (kind, color)
+        }
+        _ => return Err("underline must be a boolean, a string or a table".into()),
+    };
+    let kind = match kind {
+        "single" | "words" | "double" | "thick" | "dotted" | "dash" | "wave" | "none" => kind,
+        "dotted-heavy" => "dottedHeavy",
+        "dashed-heavy" => "dashedHeavy",
+        "dash-long" => "dashLong",
+        "dot-dash" => "dotDash",
+        "dot-dot-dash" => "dotDotDash",
+        "wavy-heavy" => "wavyHeavy",
+        "wavy-double" => "wavyDouble",
+        _ => return Err(format!("Invalid underline style: {kind}")),
+    };
+    let color = color.map_or(String::new(), |c| format!(r#" w:color="{c}""#));
+    Ok(format!(r#"<w:u w:val="{kind}"{color} />"#))
+}
+
+/// 段落边框：`box`、`none`、以空格分隔的 `top`/`left`/`bottom`/`right`，
+/// 或 `{ sides = 边, color = 颜色, width = 磅 }`
+fn border(val: &Val) -> Result<String, String> {
+    let (sides, color, width) = match val {
+        Val::String(sides) => (sides.as_str(), "auto".into(), 0.5),
+        Val::Table(border) => {
+            let sides = match border.get("sides") {
+                Some(Val::String(sides)) => sides.as_str(),
+                Some(_) => return Err("border.sides must be a string".into()),
+                None => "box",
+            };
+            let color = match border.get("color") {
+                Some(c) => color(c, "border.color")?,
+                None => "auto".into(),
+            };
+            let width = match border.get("width") {
+                Some(w) => Length::points(w, "border.width")?,
+                None => 0.5,
+            };
+            (sides, color, width)
+        }
+        _ => return Err("border must be a string or a table".into()),
+    };
+    const SIDES: [&str; 4] = ["top", "left", "bottom", "right"];
+    let sides = match sides {
+        "box" => SIDES.to_vec(),
+        "none" => Vec::new(),
+        _ => sides
+            .split_whitespace()
+            .map(|side| match SIDES.iter().find(|&&s| s == side) {
+                Some(side) => Ok(*side),
+                None => Err(format!(
+                    "Invalid border side: {side}, must be box, none, top, left, bottom or right"
+                )),
+            })
+            .collect::<Result<_, _>>()?,
+    };
+    // 边框宽度以 1/8 磅为单位，Word 只接受 0.25 到 12 磅
+    let size = ((width * 8.).round() as isize).clamp(2, 96);
+    let mut element = String::from("<w:pBdr>");
+    for side in SIDES {
+        if sides.contains(&side) {
+            write!(
+                element,
+                r#"<w:{side} w:val="single" w:sz="{size}" w:space="4" w:color="{color}" />"#
+            )
+            .unwrap();
+        } else {
+            write!(element, r#"<w:{side} w:val="nil" />"#).unwrap();
+        }
+    }
+    element.push_str("</w:pBdr>");
+    Ok(element)
+}
+
+/// 突出显示只能使用 Word 预设的颜色名，任意颜色请用 `shading`
+fn highlight(val: &Val) -> Result<String, String> {
+    const COLORS: &[&str] = &[
+        "black",
+        "blue",
+        "cyan",
+        "green",
+        "magenta",
+        "red",
+        "yellow",
+        "white",
+        "darkBlue",
+        "darkCyan",
+        "darkGreen",
+        "darkMagenta",
+        "darkRed",
+        "darkYellow",
+        "darkGray",
+        "lightGray",
+        "none",
+    ];
+    let Val::String(highlight) = val else {
+        return Err("highlight must be a string".into());
+    };
+    let name = COLORS
+        .iter()
+        .find(|c| c.eq_ignore_ascii_case(&highlight.replace('-', "")))
+        .ok_or_else(|| format!("Invalid highlight color: {highlight}"))?;
+    Ok(format!(r#"<w:highlight w:val="{name}" />"#))
+}
+
+/// 着重号
+fn emphasis_mark(val: &Val) -> Result<String, String> {
+    let mark = match val {
+        Val::Boolean(true) => "dot",
+        Val::Boolean(false) => "none",
+        Val::String(mark) => match mark.as_str() {
+            "dot" | "comma" | "circle" | "none" => mark,
+            "under-dot" => "underDot",
+            _ => return Err(format!("Invalid emphasis-mark: {mark}")),
+        },
+        _ => return Err("emphasis-mark must be a boolean or a string".into()),
+    };
+    Ok(format!(r#"<w:em w:val="{mark}" />"#))
+}
+
+/// 行距：数字或 `1.5行` 表示倍数，带绝对长度单位的字符串表示固定值，
+/// 表可以是 `{ exact = 长度 }`、`{ at-least = 长度 }` 或 `{ multiple = 倍数 }`
+fn line_spacing(val: &Val) -> Result<String, String> {
+    let (rule, line) = match val {
+        Val::Table(spacing) => match spacing.iter().collect::<Vec<_>>()[..] {
+            [(rule, val)] => match rule.as_str() {
+                "exact" => ("exact", twips(Length::points(val, "line-spacing.exact")?)),
+                "at-least" => (
+                    "atLeast",
+                    twips(Length::points(val, "line-spacing.at-least")?),
+                ),
+                "multiple" => ("auto", multiple(number(val, "line-spacing.multiple")?)),
+                rule => return Err(format!("Invalid line-spacing rule: {rule}")),
             },
-            _ => panic!("Invalid alignment: {align}"),
+            _ => {
+                return Err(
+                    "line-spacing table must have exactly one of exact, at-least or multiple"
+                        .into(),
+                )
+            }
         },
-        _ => panic!("align must be a string"),
+        _ => match Length::from_val(val, "line-spacing", Unit::Lines)? {
+            Length::Lines(lines) => ("auto", multiple(lines)),
+            Length::Points(pt) => ("exact", twips(pt)),
+            Length::Chars(_) => {
+                return Err("line-spacing must be in lines or an absolute length".into())
+            }
+        },
+    };
+    Ok(format!(
+        r#"<w:spacing w:line="{line}" w:lineRule="{rule}" />"#
+    ))
+}
+
+/// 行距倍数，以 1/240 行为单位
+fn multiple(lines: f64) -> isize {
+    (lines * 240.).round() as _
+}
+
+/// 段前段后间距，数字单位为磅，也可以用 `行` 为单位
+fn space(side: &str, key: &str, val: &Val) -> Result<String, String> {
+    // 以行为单位的间距优先于绝对间距，因此设置其中一种时将另一种清零
+    let spacing = match Length::from_val(val, key, Unit::Points)? {
+        Length::Points(pt) => format!(r#"w:{side}="{}" w:{side}Lines="0""#, twips(pt)),
+        Length::Lines(lines) => format!(r#"w:{side}Lines="{}""#, hundredths(lines)),
+        Length::Chars(_) => return Err(format!("{key} must be in lines or an absolute length")),
+    };
+    Ok(format!("<w:spacing {spacing} />"))
+}
+
+/// 缩进，数字单位为字符，也可以用绝对长度
+fn indent(side: &str, key: &str, val: &Val) -> Result<String, String> {
+    // 以字符为单位的缩进优先于绝对缩进，因此设置绝对缩进时将字符缩进清零
+    let mut indent = match Length::from_val(val, key, Unit::Chars)? {
+        Length::Chars(chars) => format!(r#"w:{side}Chars="{}""#, hundredths(chars)),
+        Length::Points(pt) => format!(r#"w:{side}="{}" w:{side}Chars="0""#, twips(pt)),
+        Length::Lines(_) => {
+            return Err(format!("{key} must be in characters or an absolute length"))
+        }
+    };
+    // 首行缩进和悬挂缩进互斥
+    match side {
+        "firstLine" => indent += r#" w:hanging="0" w:hangingChars="0""#,
+        "hanging" => indent += r#" w:firstLine="0" w:firstLineChars="0""#,
+        _ => {}
     }
+    Ok(format!("<w:ind {indent} />"))
 }
 
-fn set_bold(mut style: docx::Style, val: &Val) -> docx::Style {
+fn string<'a>(val: &'a Val, key: &str) -> Result<&'a str, String> {
     match val {
-        Val::Boolean(true) => style.run_property = style.run_property.bold(),
-        Val::Boolean(false) => style.run_property = style.run_property.disable_bold(),
-        _ => panic!("bold must be a boolean"),
+        Val::String(s) => Ok(s),
+        _ => Err(format!("{key} must be a string")),
     }
-    style
 }
 
-fn set_italic(mut style: docx::Style, val: &Val) -> docx::Style {
-    match val {
-        Val::Boolean(true) => style.run_property = style.run_property.italic(),
-        Val::Boolean(false) => style.run_property = style.run_property.disable_italic(),
-        _ => panic!("bold must be a boolean"),
+fn number(val: &Val, key: &str) -> Result<f64, String> {
+    match *val {
+        Val::Integer(n) => Ok(n as _),
+        Val::Float(n) => Ok(n),
+        _ => Err(format!("{key} must be a number")),
+    }
+}
+
+/// 记录 docx-rs 不支持的属性，`container` 为 `w:pPr` 或 `w:rPr`
+fn patch(id: &str, container: &'static str, element: String) {
+    STYLE_PATCHES
+        .lock()
+        .unwrap()
+        .push((id.into(), container, element));
+}
+
+/// 去掉突出显示的代码行的样式 ID 前缀，加上底色
+pub fn apply_highlighted_lines(document: &mut Vec<u8>, fill: &str) {
+    let mut text = String::from_utf8(std::mem::take(document)).unwrap();
+    let shading = format!(r#"<w:shd w:val="clear" w:color="auto" w:fill="{fill}" />"#);
+    let marker = format!(r#"<w:pStyle w:val="{HIGHLIGHTED_CODE_MARKER}"#);
+    while let Some(i) = text.find(&marker) {
+        let head = text[..i].rfind("<w:pPr>").unwrap();
+        let range = xml::find(&text[head..], "w:pPr").unwrap();
+        let range = head + range.start..head + range.end;
+        let properties = text[range.clone()].replacen(HIGHLIGHTED_CODE_MARKER, "", 1);
+        let properties = xml::set_child(&properties, &shading, PARAGRAPH_PROPERTY_ORDER);
+        text.replace_range(range, &properties);
+    }
+    *document = text.into_bytes();
+}
+
+/// 将 docx-rs 不支持的属性补充到 styles.xml 中
+pub fn apply_style_patches(styles: &mut Vec<u8>) {
+    let mut text = String::from_utf8(std::mem::take(styles)).unwrap();
+    for (id, container, element) in &*STYLE_PATCHES.lock().unwrap() {
+        let head = text
+            .find(&format!(r#" w:styleId="{id}">"#))
+            .and_then(|i| text[..i].rfind("<w:style "))
+            .unwrap();
+        let range = xml::find(&text[head..], "w:style").unwrap();
+        let range = head + range.start..head + range.end;
+        let style = &text[range.clone()];
+
+        // 样式的 pPr 中也可能有 rPr，因此只在直接子元素中查找
+        let properties = xml::children(style)
+            .into_iter()
+            .find(|c| xml::name(&style[c.clone()]) == *container)
+            .unwrap();
+        let order = match *container {
+            "w:pPr" => PARAGRAPH_PROPERTY_ORDER,
+            _ => RUN_PROPERTY_ORDER,
+        };
+        // 有子元素的属性（如边框）整体替换，其他属性合并
+        let patched = if xml::children(element).is_empty() {
+            xml::merge_child(&style[properties.clone()], element, order)
+        } else {
+            xml::set_child(&style[properties.clone()], element, order)
+        };
+        let style = format!(
+            "{}{patched}{}",
+            &style[..properties.start],
+            &style[properties.end..]
+        );
+        text.replace_range(range, &style);
+    }
+    *styles = text.into_bytes();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use docx::StyleType::{Character, Paragraph};
+
+    fn check(id: &str, style_type: docx::StyleType, key: &str, val: Val) -> Result<(), String> {
+        super::check(id, style_type, key, &val)
+    }
+
+    #[test]
+    fn accepts_code_settings_on_code_only() {
+        let theme = || Val::String("none".into());
+        assert_eq!(check("Code", Paragraph, "syntax-theme", theme()), Ok(()));
+        assert_eq!(
+            check("BodyText", Paragraph, "syntax-theme", theme()),
+            Err("`syntax-theme` can only be set on `Code`, not `BodyText`".into())
+        );
+        assert!(check("Code-rust", Paragraph, "line-numbers", Val::Boolean(true)).is_err());
+    }
+
+    #[test]
+    fn accepts_layout_on_code_and_quote() {
+        let layout = || Val::String("paragraphs".into());
+        assert_eq!(check("Code", Paragraph, "layout", layout()), Ok(()));
+        assert_eq!(check("Quote", Paragraph, "layout", layout()), Ok(()));
+        assert!(check("Quote2", Paragraph, "layout", layout()).is_err());
+    }
+
+    #[test]
+    fn rejects_paragraph_settings_on_character_styles() {
+        assert_eq!(
+            check("InlineCode", Character, "background", Val::String("EEEEEE".into())),
+            Err("`background` is a paragraph setting and can't be set on character style `InlineCode`".into())
+        );
+        assert!(check(
+            "InlineCode",
+            Character,
+            "first-line-indent",
+            Val::Integer(2)
+        )
+        .is_err());
+        assert_eq!(
+            check(
+                "InlineCode",
+                Character,
+                "shading",
+                Val::String("EEEEEE".into())
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check(
+                "Note",
+                Paragraph,
+                "background",
+                Val::String("EEEEEE".into())
+            ),
+            Ok(())
+        );
     }
-    style
 }
@@ -1,11 +1,24 @@
-﻿use super::{
+use super::{
+    code::{
+        diff_lines, is_diff, keep_together, line_highlight, paragraph_layout, paragraphs, source,
+        Diff, Meta,
+    },
     docx, md,
-    style::{caption_style, code_style, table_style},
+    style::{
+        caption_style, diff_style, highlighted_code_style, line_number_style, quote_style,
+        quote_table_layout, table_style, BASE_CODE_STYLE_ID,
+    },
     Ast,
 };
 use std::path::Path;
 
-pub fn from_code(code: md::Code) -> (docx::Table, docx::Paragraph) {
+/// 代码块和引用排版为表格或一组段落
+pub enum Block {
+    Table(Box<docx::Table>),
+    Paragraphs(Vec<docx::Paragraph>),
+}
+
+pub fn from_code(code: md::Code, dir: &Path) -> (Block, docx::Paragraph) {
     use docx::{Table, TableCell, TableRow};
 
     #[inline(always)]
@@ -17,24 +30,161 @@ pub fn from_code(code: md::Code) -> (docx::Table, docx::Paragraph) {
         value, lang, meta, ..
     } = code;
 
-    let lang = lang.unwrap_or_default();
-    let mut code = TableCell::new();
-    for line in value.lines() {
-        code = code.add_paragraph(code_style(paragraph_from(line.to_string()), &lang));
+    let meta = Meta::parse(lang, meta);
+    let value = source(value, &meta, dir);
+    let mut lines = paragraphs(&value, &meta.lang);
+    let caption = caption_style(paragraph_from(meta.caption.clone()));
+    // 除最后一行外都与下一段同页，使代码块不跨页
+    if keep_together() {
+        let last = lines.len().saturating_sub(1);
+        for p in &mut lines[..last] {
+            p.property = p.property.clone().keep_next(true);
+        }
+    }
+
+    // 差异代码块按行的类型使用不同的样式
+    let diffs = match is_diff(&meta.lang) {
+        true => diff_lines(&value),
+        false => vec![Diff::Context; lines.len()],
+    };
+    let start = meta.start();
+    let highlighted = meta.highlighted(lines.len());
+    let digits = (start.unwrap_or(1) + lines.len().max(1) - 1)
+        .to_string()
+        .len();
+    if paragraph_layout() {
+        let lines = lines
+            .into_iter()
+            .zip(highlighted)
+            .zip(diffs)
+            .enumerate()
+            .map(|(i, ((mut p, highlighted), diff))| {
+                if let Some(start) = start {
+                    let number = line_number_style(
+                        docx::Run::new().add_text(format!("{:>digits$}  ", start + i)),
+                    );
+                    p.children
+                        .insert(0, docx::ParagraphChild::Run(Box::new(number)));
+                }
+                let p = diff_style(p, &meta.lang, diff);
+                match highlighted {
+                    true => highlighted_code_style(p),
+                    false => p,
+                }
+            })
+            .collect();
+        return (Block::Paragraphs(lines), caption);
+    }
+
+    let lines = lines
+        .into_iter()
+        .zip(diffs)
+        .map(|(p, diff)| diff_style(p, &meta.lang, diff))
+        .collect::<Vec<_>>();
+    if lines.is_empty() || (start.is_none() && !highlighted.contains(&true)) {
+        let code = lines
+            .into_iter()
+            .fold(TableCell::new(), |cell, p| cell.add_paragraph(p));
+        return (
+            Block::Table(Box::new(table_style(Table::new(vec![TableRow::new(
+                vec![code],
+            )])))),
+            caption,
+        );
     }
 
-    (
-        table_style(Table::new(vec![TableRow::new(vec![code])])),
-        caption_style(paragraph_from(meta.unwrap_or_default())),
-    )
+    // 每行代码一行表格，折行时行号仍与代码对齐
+    let shading = docx::Shading::new().fill(line_highlight());
+    let rows = lines
+        .into_iter()
+        .zip(highlighted)
+        .enumerate()
+        .map(|(i, (mut p, highlighted))| {
+            let mut cells = Vec::with_capacity(2);
+            if let Some(start) = start {
+                let number = docx::Paragraph::new()
+                    .add_run(line_number_style(
+                        docx::Run::new().add_text((start + i).to_string()),
+                    ))
+                    .style(BASE_CODE_STYLE_ID)
+                    .align(docx::AlignmentType::Right);
+                cells.push(
+                    TableCell::new()
+                        .add_paragraph(number)
+                        .width(digits * 160 + 240, docx::WidthType::Dxa),
+                );
+            }
+            if highlighted {
+                p = highlighted_code_style(p);
+            }
+            cells.push(TableCell::new().add_paragraph(p));
+            if highlighted {
+                cells = cells
+                    .into_iter()
+                    .map(|cell| cell.shading(shading.clone()))
+                    .collect();
+            }
+            TableRow::new(cells)
+        })
+        .collect();
+    let table = Table::new(rows).clear_border(docx::TableBorderPosition::InsideH);
+    (Block::Table(Box::new(table_style(table))), caption)
 }
 
-pub fn from_block_quote(quote: md::BlockQuote, dir: &Path) -> docx::Table {
+pub fn from_block_quote(quote: md::BlockQuote, dir: &Path) -> Vec<Block> {
     use docx::{Table, TableRow};
     let md::BlockQuote { children, .. } = quote;
-    table_style(Table::new(vec![TableRow::new(vec![cell_from(
-        children, dir,
-    )])]))
+    if quote_table_layout() {
+        let table = table_style(Table::new(vec![TableRow::new(vec![cell_from(
+            children, dir,
+        )])]));
+        return vec![
+            Block::Table(Box::new(table)),
+            Block::Paragraphs(vec![docx::Paragraph::new()]),
+        ];
+    }
+    quote_blocks(children, dir, 1)
+}
+
+/// 第 `depth` 层引用中的内容，段落使用该层的引用样式
+fn quote_blocks(children: impl IntoIterator<Item = Ast>, dir: &Path, depth: usize) -> Vec<Block> {
+    use super::{
+        list::from_list,
+        paragraph::{from_heading, from_paragraph},
+    };
+
+    let mut blocks = Vec::new();
+    for ast in children {
+        match ast {
+            Ast::Paragraph(paragraph) => {
+                let (paragraph, caption) = from_paragraph(paragraph, dir);
+                let mut lines = vec![quote_style(paragraph, depth)];
+                lines.extend(caption);
+                blocks.push(Block::Paragraphs(lines));
+            }
+            Ast::Code(code) => {
+                let (code, caption) = from_code(code, dir);
+                blocks.push(code);
+                blocks.push(Block::Paragraphs(vec![caption]));
+            }
+            Ast::BlockQuote(md::BlockQuote { children, .. }) => {
+                blocks.extend(quote_blocks(children, dir, depth + 1));
+            }
+            Ast::Table(table) => blocks.push(Block::Table(Box::new(from_table(table)))),
+            Ast::List(list) => blocks.push(Block::Paragraphs(
+                from_list(list, dir)
+                    .into_iter()
+                    .map(|p| quote_style(p, depth))
+                    .collect(),
+            )),
+            Ast::Heading(heading) => blocks.push(Block::Paragraphs(vec![from_heading(heading)])),
+
+            Ast::Root(_) => unreachable!(),
+
+            ast => skip_in_quote(&ast),
+        }
+    }
+    blocks
 }
 
 pub fn from_table(table: md::Table) -> docx::Table {
@@ -81,7 +231,10 @@ pub fn from_table(table: md::Table) -> docx::Table {
 }
 
 fn cell_from(children: impl IntoIterator<Item = Ast>, dir: &Path) -> docx::TableCell {
-    use super::{list::from_list, paragraph::from_paragraph};
+    use super::{
+        list::from_list,
+        paragraph::{from_heading, from_paragraph},
+    };
 
     let mut cell = docx::TableCell::new();
     for ast in children {
@@ -94,21 +247,47 @@ fn cell_from(children: impl IntoIterator<Item = Ast>, dir: &Path) -> docx::Table
                 }
             }
             Ast::Code(code) => {
-                let (code, caption) = from_code(code);
-                cell.add_table(code).add_paragraph(caption)
+                let (code, caption) = from_code(code, dir);
+                match code {
+                    Block::Table(table) => cell.add_table(*table),
+                    Block::Paragraphs(lines) => lines
+                        .into_iter()
+                        .fold(cell, |cell, p| cell.add_paragraph(p)),
+                }
+                .add_paragraph(caption)
+            }
+            Ast::BlockQuote(quote) => {
+                from_block_quote(quote, dir)
+                    .into_iter()
+                    .fold(cell, |cell, block| match block {
+                        Block::Table(table) => cell.add_table(*table),
+                        Block::Paragraphs(lines) => lines
+                            .into_iter()
+                            .fold(cell, |cell, p| cell.add_paragraph(p)),
+                    })
             }
-            Ast::BlockQuote(quote) => cell
-                .add_table(from_block_quote(quote, dir))
-                .add_paragraph(docx::Paragraph::new()),
             Ast::Table(table) => cell.add_table(from_table(table)),
             Ast::List(list) => from_list(list, dir)
                 .into_iter()
                 .fold(cell, |cell, p| cell.add_paragraph(p)),
+            Ast::Heading(heading) => cell.add_paragraph(from_heading(heading)),
 
-            Ast::Root(_) | Ast::Heading(_) => unreachable!(),
+            Ast::Root(_) => unreachable!(),
 
-            _ => todo!(),
+            ast => {
+                skip_in_quote(&ast);
+                cell
+            }
         };
     }
     cell
 }
+
+/// 引用中无法转换的内容，如分隔线和 HTML，跳过并给出警告
+fn skip_in_quote(ast: &Ast) {
+    let line = ast.position().map_or(0, |p| p.start.line);
+    match ast.to_string().trim() {
+        "" => eprintln!("Skipped unsupported content in block quote at line {line}"),
+        text => eprintln!("Skipped unsupported content in block quote at line {line}: {text}"),
+    }
+}
@@ -0,0 +1,664 @@
+//! 以已有的 docx 作为模板：沿用其样式、编号、主题、字体、页眉页脚和页面设置，只替换正文
+
+use super::{
+    section::SECTION_PROPERTY_ORDER,
+    style::{Property, PARAGRAPH_PROPERTY_ORDER, RUN_PROPERTY_ORDER},
+    xml,
+};
+use docx_rs::XMLDocx;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, Write},
+    path::Path,
+};
+use zip::{write::FileOptions, ZipArchive, ZipWriter};
+
+/// 转换结果中的部件改名时使用的前缀，避免与模板中的部件和关系 ID 冲突
+const PREFIX: &str = "md2docx";
+const DOCUMENT: &str = "word/document.xml";
+const DOCUMENT_RELS: &str = "word/_rels/document.xml.rels";
+const STYLES: &str = "word/styles.xml";
+const NUMBERING: &str = "word/numbering.xml";
+const SETTINGS: &str = "word/settings.xml";
+const CONTENT_TYPES: &str = "[Content_Types].xml";
+const NUMBERING_TYPE: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/numbering";
+
+const STYLE_ORDER: &[&str] = &[
+    "w:name",
+    "w:aliases",
+    "w:basedOn",
+    "w:next",
+    "w:link",
+    "w:autoRedefine",
+    "w:hidden",
+    "w:uiPriority",
+    "w:semiHidden",
+    "w:unhideWhenUsed",
+    "w:qFormat",
+    "w:locked",
+    "w:personal",
+    "w:personalCompose",
+    "w:personalReply",
+    "w:rsid",
+    "w:pPr",
+    "w:rPr",
+    "w:tblPr",
+    "w:trPr",
+    "w:tcPr",
+    "w:tblStylePr",
+];
+/// 设置中排在 `w:evenAndOddHeaders` 之后的元素
+const AFTER_EVEN_AND_ODD_HEADERS: &[&str] = &[
+    "w:bookFoldRevPrinting",
+    "w:bookFoldPrinting",
+    "w:bookFoldPrintingSheets",
+    "w:drawingGridHorizontalSpacing",
+    "w:drawingGridVerticalSpacing",
+    "w:displayHorizontalDrawingGridEvery",
+    "w:displayVerticalDrawingGridEvery",
+    "w:doNotUseMarginsForDrawingGridOrigin",
+    "w:drawingGridHorizontalOrigin",
+    "w:drawingGridVerticalOrigin",
+    "w:doNotShadeFormData",
+    "w:noPunctuationKerning",
+    "w:characterSpacingControl",
+    "w:printTwoOnOne",
+    "w:strictFirstAndLastChars",
+    "w:noLineBreaksAfter",
+    "w:noLineBreaksBefore",
+    "w:savePreviewPicture",
+    "w:doNotValidateAgainstSchema",
+    "w:saveInvalidXml",
+    "w:ignoreMixedContent",
+    "w:alwaysShowPlaceholderText",
+    "w:doNotDemarcateInvalidXml",
+    "w:saveXmlDataOnly",
+    "w:useXSLTWhenSaving",
+    "w:saveThroughXslt",
+    "w:showXMLTags",
+    "w:alwaysMergeEmptyNamespace",
+    "w:updateFields",
+    "w:hdrShapeDefaults",
+    "w:footnotePr",
+    "w:endnotePr",
+    "w:compat",
+    "w:docVars",
+    "w:rsids",
+    "m:mathPr",
+    "w:attachedSchema",
+    "w:themeFontLang",
+    "w:clrSchemeMapping",
+    "w:doNotIncludeSubdocsInStats",
+    "w:doNotAutoCompressPictures",
+    "w:forceUpgrade",
+    "w:captions",
+    "w:readModeInkLockDown",
+    "w:smartTagType",
+    "sl:schemaLibrary",
+    "w:shapeDefaults",
+    "w:doNotEmbedSmartTags",
+    "w:decimalSymbol",
+    "w:listSeparator",
+];
+
+/// 模板 docx 中的所有部件
+pub struct Template {
+    parts: Vec<(String, Vec<u8>)>,
+    /// 正文插入处的占位段落文字或书签名，为空时替换模板的整个正文
+    placeholder: Option<String>,
+}
+
+impl Template {
+    pub fn open(path: &Path) -> Self {
+        let file = File::open(path)
+            .unwrap_or_else(|e| panic!("Failed to open reference docx {}: {e}", path.display()));
+        let mut zip = ZipArchive::new(file)
+            .unwrap_or_else(|e| panic!("Invalid reference docx {}: {e}", path.display()));
+        let parts = (0..zip.len())
+            .filter_map(|i| {
+                let mut part = zip.by_index(i).unwrap();
+                if part.is_dir() {
+                    return None;
+                }
+                let mut content = Vec::new();
+                part.read_to_end(&mut content).unwrap();
+                Some((part.name().to_string(), content))
+            })
+            .collect::<Vec<_>>();
+        let template = Self {
+            parts,
+            placeholder: None,
+        };
+        if template.get(DOCUMENT).is_none() {
+            panic!("Invalid reference docx {}: no {DOCUMENT}", path.display());
+        }
+        template
+    }
+
+    /// 保留模板正文，将转换结果插入到占位段落或书签处
+    pub fn fill_at(self, placeholder: String) -> Self {
+        Self {
+            placeholder: Some(placeholder),
+            ..self
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.parts
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, content)| std::str::from_utf8(content).unwrap())
+    }
+
+    /// 用模板的页面设置替换正文的节属性，转换结果中设置了页眉页脚时仍使用转换结果的页眉页脚
+    ///
+    /// 需要在 `section::apply_sections` 之前调用，以便分节符也使用模板的页面设置。
+    /// 转换结果中的关系 ID 在此加上前缀，模板节属性中的关系 ID 保持不变。
+    pub fn apply_page_setup(&self, document: &mut Vec<u8>) {
+        let mut text = String::from_utf8(std::mem::take(document)).unwrap();
+        for kind in ["Header", "Footer", "Image", "Hyperlink"] {
+            text = text.replace(&format!(r#""rId{kind}"#), &format!(r#""{PREFIX}{kind}"#));
+        }
+        let ours = xml::find(&text, "w:sectPr").unwrap();
+        let mut section = body_section(self.get(DOCUMENT).unwrap());
+
+        let references = ["w:headerReference", "w:footerReference", "w:titlePg"];
+        let children = xml::children(&text[ours.clone()]);
+        let own = children
+            .iter()
+            .map(|c| &text[ours.start + c.start..ours.start + c.end])
+            .filter(|c| references.contains(&xml::name(c)))
+            .collect::<Vec<_>>();
+        if !own.is_empty() {
+            for name in references {
+                section = xml::remove_child(&section, name, SECTION_PROPERTY_ORDER);
+            }
+            for element in own {
+                section = xml::set_child(&section, element, SECTION_PROPERTY_ORDER);
+            }
+        }
+        text.replace_range(ours, &section);
+        *document = text.into_bytes();
+    }
+
+    /// 将转换结果放入模板，写出 docx
+    ///
+    /// `overridden` 中的样式在样式文件中设置过，设置过的属性覆盖模板中的同名样式。
+    pub fn pack<W: Write + Seek>(
+        &self,
+        xml: XMLDocx,
+        overridden: &[(String, Vec<Property>)],
+        w: W,
+    ) {
+        // 只解码要修改的 xml 部件，图片和字体等二进制部件原样写出
+        let mut parts = self.parts.clone();
+        let mut has_media = false;
+        let part =
+            |parts: &[(String, Vec<u8>)], name: &str| parts.iter().position(|(n, _)| n == name);
+        let text = |content: &[u8]| std::str::from_utf8(content).unwrap().to_string();
+
+        let mut body = body(&String::from_utf8(xml.document.clone()).unwrap()).to_string();
+        let mut rels = String::from_utf8(xml.document_rels).unwrap();
+        let mut content_types = Vec::new();
+
+        // 样式：按 ID 或名称匹配模板中的样式，未匹配的样式加入模板
+        let i = part(&parts, STYLES).expect("No styles in reference docx");
+        let (styles, renames) = merge_styles(
+            &text(&parts[i].1),
+            &String::from_utf8(xml.styles).unwrap(),
+            overridden,
+        );
+        parts[i].1 = styles.into_bytes();
+        body = rename_styles(&body, &renames);
+
+        // 编号：编号 ID 接在模板的编号之后
+        let numbering = String::from_utf8(xml.numberings).unwrap();
+        match part(&parts, NUMBERING) {
+            Some(i) => {
+                let (merged, offset) = merge_numbering(&text(&parts[i].1), &numbering);
+                parts[i].1 = merged.into_bytes();
+                body = shift_num_ids(&body, offset);
+            }
+            None => {
+                parts.push((NUMBERING.into(), numbering.into_bytes()));
+                content_types.push(format!(
+                    r#"<Override PartName="/{NUMBERING}" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.numbering+xml" />"#
+                ));
+                rels = rels.replace(
+                    "</Relationships>",
+                    &format!(
+                        r#"<Relationship Id="{PREFIX}Numbering" Type="{NUMBERING_TYPE}" Target="numbering.xml" /></Relationships>"#
+                    ),
+                );
+            }
+        }
+
+        // 页眉页脚和图片等部件改名后加入模板
+        for (kind, contents, part_rels) in [
+            ("header", xml.headers, xml.header_rels),
+            ("footer", xml.footers, xml.footer_rels),
+        ] {
+            for (i, content) in contents.into_iter().enumerate() {
+                let name = format!("{PREFIX}-{kind}{}.xml", i + 1);
+                rels = rels.replace(
+                    &format!(r#"Target="{kind}{}.xml""#, i + 1),
+                    &format!(r#"Target="{name}""#),
+                );
+                content_types.push(format!(
+                    r#"<Override PartName="/word/{name}" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.{kind}+xml" />"#
+                ));
+                let content = String::from_utf8(content).unwrap();
+                parts.push((
+                    format!("word/{name}"),
+                    rename_styles(&content, &renames).into_bytes(),
+                ));
+                if let Some(r) = part_rels.get(i) {
+                    parts.push((format!("word/_rels/{name}.rels"), r.clone()));
+                }
+            }
+        }
+        for (id, content) in xml.media {
+            let name = format!("media/{}.png", id.replacen("rId", &format!("{PREFIX}-"), 1));
+            rels = rels.replace(
+                &format!(r#"Target="media/{id}.png""#),
+                &format!(r#"Target="{name}""#),
+            );
+            parts.push((format!("word/{name}"), content));
+            has_media = true;
+        }
+
+        // 转换结果的关系：只保留页眉页脚、图片和超链接，ID 加上前缀
+        let ours = rels
+            .split("<Relationship ")
+            .skip(1)
+            .map(|r| format!("<Relationship {}", r.split("/>").next().unwrap()))
+            .filter(|r| {
+                ["Header", "Footer", "Image", "Hyperlink", "Numbering"]
+                    .iter()
+                    .any(|kind| {
+                        r.contains(&format!(r#"Id="rId{kind}"#))
+                            || r.contains(&format!(r#"Id="{PREFIX}{kind}"#))
+                    })
+            })
+            .map(|r| format!("{}/>", r.replace(r#"Id="rId"#, &format!(r#"Id="{PREFIX}"#))))
+            .collect::<String>();
+        let i = part(&parts, DOCUMENT_RELS).expect("No document rels in reference docx");
+        parts[i].1 = text(&parts[i].1)
+            .replace("</Relationships>", &format!("{ours}</Relationships>"))
+            .into_bytes();
+
+        // 正文
+        let i = part(&parts, DOCUMENT).unwrap();
+        let document = &text(&parts[i].1);
+        let document = match &self.placeholder {
+            Some(placeholder) => {
+                // 插入处之后的内容沿用模板最后的节属性
+                if let Some(at) = body.rfind("<w:sectPr") {
+                    body.truncate(at);
+                }
+                fill(document, &body, placeholder)
+            }
+            None => {
+                let range = body_range(document);
+                format!(
+                    "{}{body}{}",
+                    &document[..range.start],
+                    &document[range.end..]
+                )
+            }
+        };
+        let document = add_namespaces(&document, &String::from_utf8(xml.document).unwrap());
+        parts[i].1 = document.into_bytes();
+
+        // 奇偶页不同的页眉页脚需要在设置中打开
+        let settings = String::from_utf8(xml.settings).unwrap();
+        if settings.contains("<w:evenAndOddHeaders") {
+            if let Some(i) = part(&parts, SETTINGS) {
+                let text = text(&parts[i].1);
+                if !text.contains("<w:evenAndOddHeaders") {
+                    let root = xml::find(&text, "w:settings").unwrap();
+                    let patched = xml::insert_before(
+                        &text[root.clone()],
+                        "<w:evenAndOddHeaders />",
+                        AFTER_EVEN_AND_ODD_HEADERS,
+                    );
+                    parts[i].1 = format!("{}{patched}{}", &text[..root.start], &text[root.end..])
+                        .into_bytes();
+                }
+            }
+        }
+
+        // 内容类型
+        let i = part(&parts, CONTENT_TYPES).expect("No content types in reference docx");
+        let types = text(&parts[i].1);
+        if has_media && !types.contains(r#"Extension="png""#) {
+            content_types.push(r#"<Default Extension="png" ContentType="image/png" />"#.into());
+        }
+        parts[i].1 = types
+            .replace("</Types>", &format!("{}</Types>", content_types.concat()))
+            .into_bytes();
+
+        let mut zip = ZipWriter::new(w);
+        let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for (name, content) in parts {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(&content).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+}
+
+/// 正文最后的节属性
+fn body_section(document: &str) -> String {
+    let start = document
+        .rfind("<w:sectPr")
+        .expect("No section property in reference docx");
+    let range = xml::find(&document[start..], "w:sectPr").unwrap();
+    document[start + range.start..start + range.end].to_string()
+}
+
+/// `w:body` 的内容在文档中的范围
+fn body_range(document: &str) -> std::ops::Range<usize> {
+    let body = xml::find(document, "w:body").expect("No body in document");
+    let text = &document[body.clone()];
+    if text[..text.find('>').unwrap()].ends_with('/') {
+        // 空的正文没有内容可替换
+        panic!("Empty body in reference docx");
+    }
+    body.start + text.find('>').unwrap() + 1..body.start + text.rfind("</").unwrap()
+}
+
+fn body(document: &str) -> &str {
+    &document[body_range(document)]
+}
+
+/// 将 `content` 插入到文字为 `placeholder` 的段落处，替换该段落；
+/// 没有这样的段落时插入到名为 `placeholder` 的书签所在段落之后
+fn fill(document: &str, content: &str, placeholder: &str) -> String {
+    let paragraphs = elements(document, "w:p");
+    let (range, keep) = match paragraphs
+        .iter()
+        .find(|p| paragraph_text(&document[(*p).clone()]).trim() == placeholder)
+    {
+        Some(p) => (p.clone(), false),
+        None => {
+            let name = format!(r#" w:name="{placeholder}""#);
+            let bookmark = elements(document, "w:bookmarkStart")
+                .into_iter()
+                .find(|b| document[b.clone()].contains(&name))
+                .unwrap_or_else(|| panic!("Placeholder not found in template: {placeholder}"));
+            // 不在段落中的书签直接在其后插入
+            let p = paragraphs
+                .into_iter()
+                .find(|p| p.start < bookmark.start && bookmark.end <= p.end)
+                .unwrap_or(bookmark);
+            (p, true)
+        }
+    };
+    let kept = if keep { &document[range.clone()] } else { "" };
+    format!(
+        "{}{kept}{content}{}",
+        &document[..range.start],
+        &document[range.end..]
+    )
+}
+
+/// 所有名为 `name` 的元素，不含嵌套在同名元素中的
+fn elements(xml: &str, name: &str) -> Vec<std::ops::Range<usize>> {
+    let mut ans = Vec::new();
+    let mut offset = 0;
+    while let Some(r) = xml::find(&xml[offset..], name) {
+        ans.push(offset + r.start..offset + r.end);
+        offset += r.end;
+    }
+    ans
+}
+
+/// 段落中所有文字
+fn paragraph_text(paragraph: &str) -> String {
+    elements(paragraph, "w:t")
+        .into_iter()
+        .map(|r| {
+            let t = &paragraph[r];
+            match t.find('>') {
+                Some(head) if !t[..head].ends_with('/') => &t[head + 1..t.rfind("</").unwrap()],
+                _ => "",
+            }
+        })
+        .collect()
+}
+
+/// 将转换结果根元素上模板缺少的命名空间声明加到模板上
+fn add_namespaces(document: &str, ours: &str) -> String {
+    // 根元素的开始标签
+    let root = |text: &str| {
+        let start = xml::find(text, "w:document").unwrap().start;
+        start..start + text[start..].find('>').unwrap() + 1
+    };
+    let template = root(document);
+    let head = &document[template.clone()];
+    let missing = xml::attrs(&ours[root(ours)])
+        .into_iter()
+        .filter(|(key, _)| key.starts_with("xmlns:") && !head.contains(&format!(" {key}=")))
+        .map(|(key, val)| format!(r#" {key}="{val}""#))
+        .collect::<String>();
+    let at = template.end - 1;
+    format!("{}{missing}{}", &document[..at], &document[at..])
+}
+
+/// 将正文或页眉页脚中引用的样式改用模板中的样式 ID
+fn rename_styles(text: &str, renames: &[(String, String)]) -> String {
+    let mut text = text.to_string();
+    for (from, to) in renames {
+        for tag in ["w:pStyle", "w:rStyle", "w:tblStyle"] {
+            text = text.replace(
+                &format!(r#"<{tag} w:val="{from}" />"#),
+                &format!(r#"<{tag} w:val="{to}" />"#),
+            );
+        }
+    }
+    text
+}
+
+/// 合并样式，返回合并后的 styles.xml 以及转换结果中需要改用模板样式 ID 的样式
+fn merge_styles(
+    template: &str,
+    ours: &str,
+    overridden: &[(String, Vec<Property>)],
+) -> (String, Vec<(String, String)>) {
+    let root = xml::find(template, "w:styles").unwrap();
+    let mut container = template[root.clone()].to_string();
+    let template_styles = xml::children(&container)
+        .into_iter()
+        .map(|c| container[c].to_string())
+        .filter(|c| xml::name(c) == "w:style")
+        .collect::<Vec<_>>();
+
+    let ours_root = &ours[xml::find(ours, "w:styles").unwrap()];
+    let our_styles = xml::children(ours_root)
+        .into_iter()
+        .map(|c| ours_root[c].to_string())
+        .filter(|c| xml::name(c) == "w:style")
+        .collect::<Vec<_>>();
+
+    // 先确定所有样式的 ID 映射，新加入的样式中的引用也要改名
+    let name = |style: &str| {
+        xml::find(style, "w:name")
+            .and_then(|r| xml::attr(&style[r], "w:val").map(str::to_lowercase))
+    };
+    let mut renames = HashMap::new();
+    let mut matched = HashMap::new();
+    for style in &our_styles {
+        let id = xml::attr(style, "w:styleId").unwrap();
+        let kind = xml::attr(style, "w:type");
+        let found = template_styles
+            .iter()
+            .filter(|t| xml::attr(t, "w:type") == kind)
+            .find(|t| xml::attr(t, "w:styleId") == Some(id))
+            .or_else(|| {
+                template_styles
+                    .iter()
+                    .filter(|t| xml::attr(t, "w:type") == kind)
+                    .find(|t| name(t).is_some() && name(t) == name(style))
+            });
+        if let Some(t) = found {
+            let target = xml::attr(t, "w:styleId").unwrap().to_string();
+            if target != id {
+                renames.insert(id.to_string(), target.clone());
+            }
+            matched.insert(id.to_string(), t.clone());
+        }
+    }
+
+    let mut added = String::new();
+    for style in &our_styles {
+        let id = xml::attr(style, "w:styleId").unwrap();
+        let mut style = style.clone();
+        for (from, to) in &renames {
+            for tag in ["w:basedOn", "w:next", "w:link"] {
+                style = style.replace(
+                    &format!(r#"<{tag} w:val="{from}" />"#),
+                    &format!(r#"<{tag} w:val="{to}" />"#),
+                );
+            }
+        }
+        match (matched.get(id), overridden.iter().find(|(o, _)| o == id)) {
+            (Some(t), Some((_, properties))) => {
+                container = container.replace(t.as_str(), &override_style(t, &style, properties));
+            }
+            (Some(_), None) => {}
+            (None, _) => added.push_str(&style),
+        }
+    }
+    container = container.replace("</w:styles>", &format!("{added}</w:styles>"));
+
+    let mut renames = renames.into_iter().collect::<Vec<_>>();
+    renames.sort();
+    (
+        format!(
+            "{}{container}{}",
+            &template[..root.start],
+            &template[root.end..]
+        ),
+        renames,
+    )
+}
+
+/// 用样式文件中设置过的段落和字符属性覆盖模板样式的对应设置，`ours` 为转换结果中的同名样式
+fn override_style(template: &str, ours: &str, properties: &[Property]) -> String {
+    // 只在样式的直接子元素中查找，段落属性中也可能有字符属性
+    let container = |style: &str, name: &str| {
+        xml::children(style)
+            .into_iter()
+            .map(|c| style[c].to_string())
+            .find(|c| xml::name(c) == name)
+    };
+    let mut style = template.to_string();
+    for property in properties {
+        let (name, element) = match property {
+            Property::Built(name, element) => {
+                let Some(element) = container(ours, name).and_then(|c| {
+                    xml::children(&c)
+                        .into_iter()
+                        .map(|r| c[r].to_string())
+                        .find(|c| xml::name(c) == *element)
+                }) else {
+                    continue;
+                };
+                (*name, element)
+            }
+            Property::Patch(name, element) => (*name, element.clone()),
+        };
+        let order = match name {
+            "w:pPr" => PARAGRAPH_PROPERTY_ORDER,
+            _ => RUN_PROPERTY_ORDER,
+        };
+        let existing = container(&style, name).unwrap_or_else(|| format!("<{name}></{name}>"));
+        // 间距和缩进等可由多项设置组成，只合并设置过的属性；字体和有子元素的属性整体替换
+        let existing = match property {
+            Property::Patch(..) if xml::children(&element).is_empty() => {
+                xml::merge_child(&existing, &element, order)
+            }
+            _ => xml::set_child(&existing, &element, order),
+        };
+        style = xml::set_child(&style, &existing, STYLE_ORDER);
+    }
+    style
+}
+
+/// 合并编号定义，返回合并后的 numbering.xml 以及转换结果中编号 ID 的偏移
+fn merge_numbering(template: &str, ours: &str) -> (String, usize) {
+    let max = |text: &str, tag: &str, attr: &str| {
+        text.match_indices(&format!("<{tag} "))
+            .filter_map(|(i, _)| xml::attr(&text[i..], attr)?.parse::<usize>().ok())
+            .max()
+            .unwrap_or(0)
+    };
+    let abstract_offset = max(template, "w:abstractNum", "w:abstractNumId") + 1;
+    let num_offset = max(template, "w:num", "w:numId");
+
+    let root = &ours[xml::find(ours, "w:numbering").unwrap()];
+    let mut abstracts = String::new();
+    let mut nums = String::new();
+    for child in xml::children(root) {
+        let child = &root[child];
+        match xml::name(child) {
+            "w:abstractNum" => {
+                let id = xml::attr(child, "w:abstractNumId")
+                    .unwrap()
+                    .parse::<usize>()
+                    .unwrap();
+                abstracts.push_str(&xml::set_attr(
+                    child,
+                    "w:abstractNumId",
+                    id + abstract_offset,
+                ));
+            }
+            "w:num" => {
+                let id = xml::attr(child, "w:numId")
+                    .unwrap()
+                    .parse::<usize>()
+                    .unwrap();
+                let num = xml::set_attr(child, "w:numId", id + num_offset);
+                let num = xml::map_child(&num, "w:abstractNumId", &[], |a| {
+                    let id = xml::attr(a, "w:val").unwrap().parse::<usize>().unwrap();
+                    xml::set_attr(a, "w:val", id + abstract_offset)
+                });
+                nums.push_str(&num);
+            }
+            _ => {}
+        }
+    }
+
+    let range = xml::find(template, "w:numbering").unwrap();
+    let container = &template[range.clone()];
+    let container = xml::insert_before(container, &abstracts, &["w:num", "w:numIdMacAtCleanup"]);
+    let container = xml::insert_before(&container, &nums, &["w:numIdMacAtCleanup"]);
+    (
+        format!(
+            "{}{container}{}",
+            &template[..range.start],
+            &template[range.end..]
+        ),
+        num_offset,
+    )
+}
+
+/// 正文中引用的编号 ID 加上偏移，0 表示不编号，保持不变
+fn shift_num_ids(body: &str, offset: usize) -> String {
+    const NUM_ID: &str = r#"<w:numId w:val=""#;
+    let mut ans = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(i) = rest.find(NUM_ID) {
+        let start = i + NUM_ID.len();
+        let len = rest[start..].find('"').unwrap();
+        let id = rest[start..start + len].parse::<usize>().unwrap();
+        ans.push_str(&rest[..start]);
+        ans.push_str(&if id == 0 { 0 } else { id + offset }.to_string());
+        rest = &rest[start + len..];
+    }
+    ans.push_str(rest);
+    ans
+}
@@ -0,0 +1,151 @@
+//! 样式值中的长度，支持 `pt`/`磅`、`mm`、`cm`、`in`、`px`、`字符`/`ch` 和 `行`/`lines` 单位
+
+use toml::Value as Val;
+
+/// 长度，绝对长度统一换算为磅
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Length {
+    Points(f64),
+    Chars(f64),
+    Lines(f64),
+}
+
+/// 不带单位的数字所使用的单位
+#[derive(Clone, Copy)]
+pub enum Unit {
+    Points,
+    Chars,
+    Lines,
+}
+
+impl Length {
+    /// 解析带单位的字符串，如 `2字符`、`1.5 lines`、`0.5cm`
+    pub fn parse(text: &str, default: Unit) -> Option<Self> {
+        let text = text.trim();
+        let split = text
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+            .unwrap_or(text.len());
+        let num = text[..split].parse::<f64>().ok()?;
+        Some(match text[split..].trim() {
+            "" => Self::new(num, default),
+            "pt" | "磅" => Self::Points(num),
+            "mm" => Self::Points(num * 72. / 25.4),
+            "cm" => Self::Points(num * 72. / 2.54),
+            "in" => Self::Points(num * 72.),
+            "px" => Self::Points(num * 0.75),
+            "ch" | "字符" | "字" => Self::Chars(num),
+            "lines" | "line" | "行" => Self::Lines(num),
+            _ => return None,
+        })
+    }
+
+    /// 从样式值读取长度，数字使用 `default` 单位，字符串可以带单位
+    pub fn from_val(val: &Val, key: &str, default: Unit) -> Result<Self, String> {
+        match val {
+            &Val::Integer(num) => Ok(Self::new(num as _, default)),
+            &Val::Float(num) => Ok(Self::new(num, default)),
+            Val::String(text) => {
+                Self::parse(text, default).ok_or_else(|| format!("Invalid {key}: {text}"))
+            }
+            _ => Err(format!("{key} must be a number or a string with unit")),
+        }
+    }
+
+    /// 绝对长度，单位为磅，不接受字符和行
+    pub fn points(val: &Val, key: &str) -> Result<f64, String> {
+        match Self::from_val(val, key, Unit::Points)? {
+            Self::Points(pt) => Ok(pt),
+            _ => Err(format!("{key} must be an absolute length")),
+        }
+    }
+
+    fn new(num: f64, unit: Unit) -> Self {
+        match unit {
+            Unit::Points => Self::Points(num),
+            Unit::Chars => Self::Chars(num),
+            Unit::Lines => Self::Lines(num),
+        }
+    }
+}
+
+/// 磅换算为 twip（1/20 磅）
+pub fn twips(pt: f64) -> isize {
+    (pt * 20.).round() as _
+}
+
+/// 磅换算为半磅
+pub fn half_points(pt: f64) -> isize {
+    (pt * 2.).round() as _
+}
+
+/// 字符数或行数换算为 1/100 字符或行
+pub fn hundredths(num: f64) -> isize {
+    (num * 100.).round() as _
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_units() {
+        assert_eq!(Length::parse("12", Unit::Points), Some(Length::Points(12.)));
+        assert_eq!(Length::parse("2", Unit::Chars), Some(Length::Chars(2.)));
+        assert_eq!(
+            Length::parse(" 1.5 lines ", Unit::Points),
+            Some(Length::Lines(1.5))
+        );
+        assert_eq!(
+            Length::parse("2字符", Unit::Points),
+            Some(Length::Chars(2.))
+        );
+        assert_eq!(
+            Length::parse("-3磅", Unit::Chars),
+            Some(Length::Points(-3.))
+        );
+        assert_eq!(Length::parse("1in", Unit::Chars), Some(Length::Points(72.)));
+        assert_eq!(Length::parse("8px", Unit::Chars), Some(Length::Points(6.)));
+        assert_eq!(
+            twips(Length::points(&Val::String("1cm".into()), "x").unwrap()),
+            567
+        );
+        assert_eq!(
+            twips(Length::points(&Val::String("10 mm".into()), "x").unwrap()),
+            567
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_lengths() {
+        assert_eq!(Length::parse("", Unit::Points), None);
+        assert_eq!(Length::parse("cm", Unit::Points), None);
+        assert_eq!(Length::parse("2 em", Unit::Points), None);
+        assert_eq!(Length::parse("1..5", Unit::Points), None);
+    }
+
+    #[test]
+    fn reads_values() {
+        let read = |val: Val| Length::from_val(&val, "indent", Unit::Chars);
+        assert_eq!(read(Val::Integer(2)), Ok(Length::Chars(2.)));
+        assert_eq!(read(Val::Float(0.5)), Ok(Length::Chars(0.5)));
+        assert_eq!(
+            read(Val::String("x".into())),
+            Err("Invalid indent: x".into())
+        );
+        assert_eq!(
+            read(Val::Boolean(true)),
+            Err("indent must be a number or a string with unit".into())
+        );
+        assert_eq!(
+            Length::points(&Val::String("2行".into()), "size"),
+            Err("size must be an absolute length".into())
+        );
+    }
+
+    #[test]
+    fn converts_to_word_units() {
+        assert_eq!(twips(10.5), 210);
+        assert_eq!(half_points(10.5), 21);
+        assert_eq!(hundredths(1.25), 125);
+    }
+}
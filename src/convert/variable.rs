@@ -0,0 +1,223 @@
+//! `{{ name }}` 形式的模板变量，在解析 Markdown 之前替换
+//!
+//! 变量依次取自 `--var`、front matter 和环境变量，`date` 默认为当天日期。
+//! 代码块、行内代码和 front matter 中的文本不替换；`\{{` 和 `{{#` 开头的写法原样保留。
+
+use super::{md, Ast};
+use std::{
+    collections::HashMap,
+    ops::Range,
+    path::Path,
+    sync::{LazyLock, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use toml::Value as Val;
+
+/// 变量的值，未初始化时（如单独检查样式文件）所有变量展开为空
+static VARIABLES: LazyLock<Mutex<Option<HashMap<String, String>>>> =
+    LazyLock::new(Default::default);
+
+/// 按 front matter 和 `--var key=value` 参数初始化变量，`--var` 优先
+pub fn init(metadata: &toml::Table, vars: &[String]) {
+    let mut variables = HashMap::from([("date".to_string(), today())]);
+    for (key, val) in metadata {
+        let val = match val {
+            Val::String(s) => s.clone(),
+            val => val.to_string(),
+        };
+        variables.insert(key.clone(), val);
+    }
+    for var in vars {
+        let Some((key, val)) = var.split_once('=') else {
+            panic!("Invalid variable: {var}, must be in the form of key=value")
+        };
+        variables.insert(key.trim().into(), val.into());
+    }
+    *VARIABLES.lock().unwrap() = Some(variables);
+}
+
+/// 替换 Markdown 文本中的变量，`root` 是替换前的解析结果，用于跳过代码，`skip` 中的文本也不替换
+///
+/// 有未定义的变量时报告所有变量及其所在行后退出。
+pub fn expand_markdown(
+    md: &str,
+    root: &md::Root,
+    mut skip: Vec<Range<usize>>,
+    file: &Path,
+) -> String {
+    verbatim(&root.children, &mut skip);
+
+    let mut undefined = Vec::new();
+    let ans = substitute(md, &skip, |at, name| {
+        lookup(name).or_else(|| {
+            undefined.push((md[..at].matches('\n').count() + 1, name.to_string()));
+            Some(String::new())
+        })
+    });
+    if !undefined.is_empty() {
+        for (line, name) in undefined {
+            eprintln!(
+                "{}:{line}: error: Undefined variable `{name}`",
+                file.display()
+            );
+        }
+        std::process::exit(1);
+    }
+    ans
+}
+
+/// 替换页眉页脚等设置文本中的变量
+pub fn expand_text(text: &str) -> String {
+    substitute(text, &[], |_, name| {
+        Some(lookup(name).unwrap_or_else(|| panic!("Undefined variable: {name}")))
+    })
+}
+
+fn lookup(name: &str) -> Option<String> {
+    match &*VARIABLES.lock().unwrap() {
+        Some(variables) => variables
+            .get(name)
+            .cloned()
+            .or_else(|| std::env::var(name).ok()),
+        None => Some(String::new()),
+    }
+}
+
+/// 将 `skip` 以外的 `{{ name }}` 替换为 `f` 的返回值，返回 `None` 时保留原文
+fn substitute(
+    text: &str,
+    skip: &[Range<usize>],
+    mut f: impl FnMut(usize, &str) -> Option<String>,
+) -> String {
+    let mut ans = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut i = 0;
+    while let Some(pos) = text[i..].find("{{") {
+        let start = i + pos;
+        i = start + 2;
+        if text[..start].ends_with('\\') || skip.iter().any(|r| r.contains(&start)) {
+            continue;
+        }
+        let Some(len) = text[i..].find("}}") else {
+            break;
+        };
+        let name = text[i..i + len].trim();
+        if !is_name(name) {
+            continue;
+        }
+        if let Some(val) = f(start, name) {
+            ans.push_str(&text[copied..start]);
+            ans.push_str(&val);
+            i += len + 2;
+            copied = i;
+        }
+    }
+    ans.push_str(&text[copied..]);
+    ans
+}
+
+/// 变量名由字母、数字、`_`、`-` 和 `.` 组成，不以数字开头，因此 `{{#include}}` 不是变量
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// 不替换变量的节点在源文本中的范围
+fn verbatim(nodes: &[Ast], ans: &mut Vec<Range<usize>>) {
+    for node in nodes {
+        match node {
+            Ast::Code(_) | Ast::InlineCode(_) | Ast::Toml(_) | Ast::Yaml(_) => {
+                if let Some(p) = node.position() {
+                    ans.push(p.start.offset..p.end.offset);
+                }
+            }
+            _ => {
+                if let Some(children) = node.children() {
+                    verbatim(children, ans);
+                }
+            }
+        }
+    }
+}
+
+/// 当天日期，`YYYY-MM-DD` 格式
+fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+        / 86400;
+    date(days)
+}
+
+/// 由 1970-01-01 起的天数换算公历日期
+fn date(days: i64) -> String {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(text: &str, skip: &[Range<usize>]) -> String {
+        substitute(text, skip, |_, name| match name {
+            "title" => Some("标题".into()),
+            "app.version" => Some("1.0".into()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn substitutes_defined_variables() {
+        assert_eq!(expand("{{title}} v{{ app.version }}", &[]), "标题 v1.0");
+        assert_eq!(expand("{{ missing }} {{title}}", &[]), "{{ missing }} 标题");
+    }
+
+    #[test]
+    fn keeps_escaped_and_non_variable_braces() {
+        assert_eq!(expand(r"\{{title}}", &[]), r"\{{title}}");
+        assert_eq!(expand("{{#include a.md}}", &[]), "{{#include a.md}}");
+        assert_eq!(expand("{{ 1st }} {{title", &[]), "{{ 1st }} {{title");
+    }
+
+    #[test]
+    fn skips_ranges() {
+        let text = "`{{title}}` {{title}} `{{title}}`";
+        assert_eq!(
+            expand(text, &[0..11, 22..33]),
+            "`{{title}}` 标题 `{{title}}`"
+        );
+    }
+
+    #[test]
+    fn reports_offsets() {
+        let mut offsets = Vec::new();
+        substitute("a {{x}}\n{{ y }}", &[], |at, _| {
+            offsets.push(at);
+            None
+        });
+        assert_eq!(offsets, [2, 8]);
+    }
+
+    #[test]
+    fn converts_days_to_dates() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(19_782), "2024-02-29");
+        assert_eq!(date(19_783), "2024-03-01");
+        assert_eq!(date(-1), "1969-12-31");
+        let today = today();
+        assert_eq!(today.len(), 10);
+        assert!(today[..4].parse::<u32>().unwrap() >= 2024);
+    }
+}
@@ -0,0 +1,292 @@
+//! 对 docx-rs 生成的 xml 片段做少量修补，用于补充 docx-rs 尚未支持的元素和属性
+
+use std::ops::Range;
+
+/// 元素的子元素，`container` 须是以开始标签起始、以结束标签结尾的完整元素
+pub fn children(container: &str) -> Vec<Range<usize>> {
+    let Some(start) = container.find('>') else {
+        return vec![];
+    };
+    if container[..start].ends_with('/') {
+        return vec![];
+    }
+    let end = container.rfind("</").unwrap();
+
+    let mut ans = Vec::new();
+    let mut depth = 0;
+    let mut child = 0;
+    let mut i = start + 1;
+    while let Some(pos) = container[i..end].find('<') {
+        let tag = i + pos;
+        let close = tag + container[tag..].find('>').unwrap() + 1;
+        if container[tag..].starts_with("</") {
+            depth -= 1;
+            if depth == 0 {
+                ans.push(child..close);
+            }
+        } else {
+            if depth == 0 {
+                child = tag;
+            }
+            if container[..close].ends_with("/>") {
+                if depth == 0 {
+                    ans.push(child..close);
+                }
+            } else {
+                depth += 1;
+            }
+        }
+        i = close;
+    }
+    ans
+}
+
+/// 元素的标签名
+pub fn name(element: &str) -> &str {
+    let end = element[1..]
+        .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+        .unwrap();
+    &element[1..1 + end]
+}
+
+/// 设置子元素：移除同名的子元素，再按 `order` 给出的顺序插入 `element`
+pub fn set_child(container: &str, element: &str, order: &[&str]) -> String {
+    let name = name(element);
+    edit_children(container, order, |children| {
+        children.retain(|c| self::name(c) != name);
+        children.push(element.to_string());
+    })
+}
+
+/// 合并子元素：已有同名子元素时，将 `element` 的属性设置到已有元素上，否则按 `order` 插入
+pub fn merge_child(container: &str, element: &str, order: &[&str]) -> String {
+    let name = name(element);
+    edit_children(container, order, |children| {
+        match children.iter_mut().find(|c| self::name(c) == name) {
+            Some(c) => {
+                for (key, val) in attrs(element) {
+                    *c = set_attr(c, key, val);
+                }
+            }
+            None => children.push(element.to_string()),
+        }
+    })
+}
+
+/// 移除所有名为 `name` 的子元素
+pub fn remove_child(container: &str, name: &str, order: &[&str]) -> String {
+    edit_children(container, order, |children| {
+        children.retain(|c| self::name(c) != name)
+    })
+}
+
+/// 修改子元素列表，然后按 `order` 稳定排序，未列出的元素排在最后
+fn edit_children(container: &str, order: &[&str], f: impl FnOnce(&mut Vec<String>)) -> String {
+    let ranges = children(container);
+    let open = match ranges.first() {
+        Some(r) => &container[..r.start],
+        None if container[..container.find('>').unwrap()].ends_with('/') => {
+            return edit_children(&expand(container), order, f)
+        }
+        None => &container[..container.rfind("</").unwrap()],
+    };
+    let close = &container[container.rfind("</").unwrap()..];
+
+    let mut children = ranges
+        .into_iter()
+        .map(|r| container[r].to_string())
+        .collect::<Vec<_>>();
+    f(&mut children);
+    children.sort_by_key(|c| {
+        let name = name(c);
+        order.iter().position(|n| *n == name).unwrap_or(order.len())
+    });
+    format!("{open}{}{close}", children.concat())
+}
+
+/// 将自闭合的空元素展开成一对开始和结束标签
+fn expand(element: &str) -> String {
+    let end = element.find("/>").unwrap();
+    format!("{}></{}>", element[..end].trim_end(), name(element))
+}
+
+/// 第一个名为 `name` 的元素在 `xml` 中的范围
+pub fn find(xml: &str, name: &str) -> Option<Range<usize>> {
+    let open = format!("<{name}");
+    let close = format!("</{name}>");
+    let is_open = |at: usize| {
+        xml[at..].starts_with(&open)
+            && xml[at + open.len()..]
+                .starts_with(|c: char| c.is_whitespace() || c == '/' || c == '>')
+    };
+    let head_end = |at: usize| at + xml[at..].find('>').unwrap() + 1;
+
+    let start = xml
+        .match_indices(&open)
+        .map(|(i, _)| i)
+        .find(|&i| is_open(i))?;
+    let head = head_end(start);
+    if xml[..head].ends_with("/>") {
+        return Some(start..head);
+    }
+    let mut depth = 1;
+    let mut i = head;
+    loop {
+        let next = i + xml[i..].find('<')?;
+        if xml[next..].starts_with(&close) {
+            depth -= 1;
+            if depth == 0 {
+                return Some(start..next + close.len());
+            }
+        } else if is_open(next) && !xml[..head_end(next)].ends_with("/>") {
+            depth += 1;
+        }
+        i = head_end(next);
+    }
+}
+
+/// 元素开始标签中的属性值
+pub fn attr<'a>(element: &'a str, name: &str) -> Option<&'a str> {
+    let head = &element[..element.find('>').unwrap()];
+    let key = format!(" {name}=\"");
+    let start = head.find(&key)? + key.len();
+    let len = head[start..].find('"').unwrap();
+    Some(&head[start..start + len])
+}
+
+/// 元素开始标签中的所有属性
+pub fn attrs(element: &str) -> Vec<(&str, &str)> {
+    let head = &element[..element.find('>').unwrap()];
+    let mut ans = Vec::new();
+    let mut rest = &head[1 + name(element).len()..];
+    while let Some(eq) = rest.find("=\"") {
+        let key = rest[..eq].trim();
+        let len = rest[eq + 2..].find('"').unwrap();
+        ans.push((key, &rest[eq + 2..eq + 2 + len]));
+        rest = &rest[eq + 3 + len..];
+    }
+    ans
+}
+
+/// 设置元素开始标签中的属性
+pub fn set_attr(element: &str, name: &str, val: impl std::fmt::Display) -> String {
+    let head = element.find('>').unwrap();
+    let key = format!(" {name}=\"");
+    match element[..head].find(&key) {
+        Some(start) => {
+            let start = start + key.len();
+            let len = element[start..].find('"').unwrap();
+            format!("{}{val}{}", &element[..start], &element[start + len..])
+        }
+        None => {
+            let end = if element[..head].ends_with('/') {
+                element[..head - 1].trim_end().len()
+            } else {
+                head
+            };
+            format!("{}{key}{val}\"{}", &element[..end], &element[end..])
+        }
+    }
+}
+
+/// 名为 `name` 的子元素，替换为 `f` 的返回值
+pub fn map_child(
+    container: &str,
+    name: &str,
+    order: &[&str],
+    f: impl Fn(&str) -> String,
+) -> String {
+    edit_children(container, order, |children| {
+        for c in children.iter_mut() {
+            if self::name(c) == name {
+                *c = f(c);
+            }
+        }
+    })
+}
+
+/// 在第一个名为 `following` 之一的子元素前插入 `elements`，没有这样的子元素时插入到最后
+///
+/// 用于元素很多、不便列出完整顺序的容器，其余子元素保持原有顺序。
+pub fn insert_before(container: &str, elements: &str, following: &[&str]) -> String {
+    if container[..container.find('>').unwrap()].ends_with('/') {
+        return insert_before(&expand(container), elements, following);
+    }
+    let at = children(container)
+        .into_iter()
+        .find(|c| following.contains(&name(&container[c.clone()])))
+        .map_or_else(|| container.rfind("</").unwrap(), |c| c.start);
+    format!("{}{elements}{}", &container[..at], &container[at..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDER: &[&str] = &["w:pStyle", "w:spacing", "w:ind", "w:jc"];
+
+    #[test]
+    fn finds_elements_by_exact_name() {
+        let xml = r#"<w:p><w:pPr/><w:r><w:p><w:t>a</w:t></w:p></w:r></w:p><w:p/>"#;
+        assert_eq!(find(xml, "w:p"), Some(0..xml.rfind("<w:p/>").unwrap()));
+        assert_eq!(find(xml, "w:pPr"), Some(5..13));
+        assert_eq!(&xml[find(xml, "w:t").unwrap()], "<w:t>a</w:t>");
+        assert_eq!(find(xml, "w:tbl"), None);
+    }
+
+    #[test]
+    fn lists_children() {
+        let xml = r#"<w:pPr><w:ind w:left="2"/><w:rPr><w:b/></w:rPr></w:pPr>"#;
+        let children = children(xml)
+            .into_iter()
+            .map(|r| &xml[r])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            children,
+            [r#"<w:ind w:left="2"/>"#, "<w:rPr><w:b/></w:rPr>"]
+        );
+        assert!(super::children("<w:pPr/>").is_empty());
+    }
+
+    #[test]
+    fn sets_children_in_order() {
+        let xml = r#"<w:pPr><w:jc w:val="left"/><w:ind w:left="2"/></w:pPr>"#;
+        assert_eq!(
+            set_child(xml, r#"<w:ind w:right="1"/>"#, ORDER),
+            r#"<w:pPr><w:ind w:right="1"/><w:jc w:val="left"/></w:pPr>"#
+        );
+        assert_eq!(
+            set_child("<w:pPr/>", r#"<w:spacing w:after="0"/>"#, ORDER),
+            r#"<w:pPr><w:spacing w:after="0"/></w:pPr>"#
+        );
+    }
+
+    #[test]
+    fn merges_attributes_into_existing_child() {
+        let xml = r#"<w:pPr><w:ind w:left="2" w:right="3"/></w:pPr>"#;
+        assert_eq!(
+            merge_child(xml, r#"<w:ind w:left="4" w:hanging="1"/>"#, ORDER),
+            r#"<w:pPr><w:ind w:left="4" w:right="3" w:hanging="1"/></w:pPr>"#
+        );
+        assert_eq!(
+            merge_child(xml, r#"<w:pStyle w:val="a"/>"#, ORDER),
+            r#"<w:pPr><w:pStyle w:val="a"/><w:ind w:left="2" w:right="3"/></w:pPr>"#
+        );
+    }
+
+    #[test]
+    fn reads_and_sets_attributes() {
+        let element = r#"<w:ind w:left="2" w:leftChars="200"/>"#;
+        assert_eq!(attr(element, "w:left"), Some("2"));
+        assert_eq!(attr(element, "w:right"), None);
+        assert_eq!(attrs(element), [("w:left", "2"), ("w:leftChars", "200")]);
+        assert_eq!(
+            set_attr(element, "w:left", 0),
+            r#"<w:ind w:left="0" w:leftChars="200"/>"#
+        );
+        assert_eq!(
+            set_attr("<w:jc/>", "w:val", "both"),
+            r#"<w:jc w:val="both"/>"#
+        );
+    }
+}
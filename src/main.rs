@@ -1,5 +1,6 @@
 mod convert;
 mod show;
+mod style;
 
 #[macro_use]
 extern crate clap;
@@ -10,6 +11,7 @@ fn main() {
     match Cli::parse().command {
         Show(args) => args.show(),
         Convert(args) => args.convert(),
+        Style(args) => args.style(),
     }
 }
 
@@ -24,4 +26,5 @@ struct Cli {
 enum Commands {
     Show(show::Args),
     Convert(convert::Args),
+    Style(style::Args),
 }
@@ -1,10 +1,12 @@
-﻿use image::ImageReader;
+﻿use crate::convert;
+use image::ImageReader;
 use markdown::{
     mdast::{
-        BlockQuote, Code, Delete, Emphasis, Heading, Image, InlineCode, Link, List, ListItem,
+        BlockQuote, Code, Delete, Emphasis, Heading, Html, Image, InlineCode, Link, List, ListItem,
         Node as Ast, Paragraph, Root, Strong, Table, TableCell, TableRow, Text, ThematicBreak,
+        Toml, Yaml,
     },
-    to_mdast, ParseOptions,
+    to_mdast, Constructs, ParseOptions,
 };
 use std::{
     fs::read_to_string,
@@ -15,15 +17,39 @@ use std::{
 pub struct Args {
     /// The file to show
     file: PathBuf,
+    /// Enabled profiles, conditional blocks for other profiles are hidden
+    #[clap(long = "profile", value_name = "PROFILE")]
+    profiles: Vec<String>,
 }
 
 impl Args {
     pub fn show(self) {
         let path = self.file;
         let md = read_to_string(&path).unwrap();
-        let Ok(Ast::Root(root)) = to_mdast(&md, &ParseOptions::gfm()) else {
+        let options = ParseOptions {
+            constructs: Constructs {
+                frontmatter: true,
+                ..Constructs::gfm()
+            },
+            ..ParseOptions::gfm()
+        };
+        let Ok(Ast::Root(mut root)) = to_mdast(&md, &options) else {
             panic!("Failed to parse markdown");
         };
+        // 列出文档中的 profile，启用的以 `*` 标记
+        let profiles = convert::profiles(&root.children);
+        if !profiles.is_empty() {
+            let list = profiles
+                .iter()
+                .map(|p| {
+                    let mark = if self.profiles.contains(p) { "*" } else { "" };
+                    format!("{mark}{p}")
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            println!("Profiles: {list}");
+        }
+        root.children = convert::filter_profiles(root.children, &self.profiles);
         let mut lines = vec![false];
         print_ast(Ast::Root(root), path.parent().unwrap(), &mut lines);
         println!();
@@ -128,11 +154,22 @@ fn print_ast(ast: Ast, dir: &Path, lines: &mut Vec<bool>) {
             };
         }
 
-        Ast::Html(_)
-        | Ast::Toml(_)
-        | Ast::Yaml(_)
-        | Ast::Math(_)
-        | Ast::Break(_)
+        Ast::Toml(Toml { value, .. }) => {
+            print!("{}Toml: {value:?}", indent(lines));
+        }
+        Ast::Yaml(Yaml { value, .. }) => {
+            print!("{}Yaml: {value:?}", indent(lines));
+        }
+
+        // 注释指令（如 `<!-- mainmatter -->`）和其他 HTML 原样显示
+        Ast::Html(Html { value, .. }) => {
+            print!("{}Html: {value:?}", indent(lines));
+        }
+        Ast::Break(_) => {
+            print!("{}Break", indent(lines));
+        }
+
+        Ast::Math(_)
         | Ast::InlineMath(_)
         | Ast::LinkReference(_)
         | Ast::ImageReference(_)
@@ -0,0 +1,75 @@
+use crate::convert::{check_style_files, style_file, used_styles, Preset};
+use std::{fs, path::PathBuf};
+
+#[derive(Args)]
+pub struct Args {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check style files and report every problem
+    Check {
+        /// Toml style files, later files may reference styles in earlier ones
+        #[clap(required = true)]
+        files: Vec<PathBuf>,
+        /// The built-in preset the style files are based on
+        #[clap(short, long, value_enum)]
+        preset: Option<Preset>,
+    },
+    /// Write a commented style file with the default settings
+    Init {
+        /// List only the styles used by this markdown file
+        #[clap(long = "for")]
+        markdown: Option<PathBuf>,
+        /// Toml style files used to convert the markdown file
+        #[clap(short, long, requires = "markdown")]
+        style: Vec<PathBuf>,
+        /// The built-in preset used to convert the markdown file
+        #[clap(short, long, value_enum, requires = "markdown")]
+        preset: Option<Preset>,
+        /// Template variables used to convert the markdown file
+        #[clap(long = "var", value_name = "KEY=VALUE", requires = "markdown")]
+        vars: Vec<String>,
+        /// Enabled profiles used to convert the markdown file
+        #[clap(long = "profile", value_name = "PROFILE", requires = "markdown")]
+        profiles: Vec<String>,
+        /// Write to this file instead of stdout
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+impl Args {
+    pub fn style(self) {
+        match self.command {
+            Command::Check { files, preset } => {
+                if !check_style_files(&files, &crate::convert::preset_settings(preset)) {
+                    std::process::exit(1)
+                }
+                println!("No problems found")
+            }
+            Command::Init {
+                markdown,
+                style,
+                preset,
+                vars,
+                profiles,
+                output,
+            } => {
+                let ids = markdown
+                    .as_deref()
+                    .map(|file| used_styles(file, preset, &style, &vars, &profiles));
+                let file = style_file(ids.as_deref());
+                match output {
+                    Some(path) if path.exists() => {
+                        panic!("{} already exists", path.display())
+                    }
+                    Some(path) => fs::write(path, file).unwrap(),
+                    None => print!("{file}"),
+                }
+            }
+        }
+    }
+}
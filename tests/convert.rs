@@ -0,0 +1,324 @@
+//! 在临时目录中运行转换命令，检查生成的 docx 中的 xml
+
+use std::{
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// 写入各文件的临时目录，每个测试使用不同的目录
+fn workspace(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("md2docx-{name}-{}", std::process::id()));
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    fs::create_dir_all(&dir).unwrap();
+    for (file, text) in files {
+        fs::write(dir.join(file), text).unwrap();
+    }
+    dir
+}
+
+/// 在 `dir` 中运行 `md2docx convert`，失败时给出错误输出
+fn convert(dir: &Path, args: &[&str]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_md2docx"))
+        .arg("convert")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// docx 中一个部件的字节
+fn bytes(docx: &Path, name: &str) -> Vec<u8> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(docx).unwrap()).unwrap();
+    let mut content = Vec::new();
+    archive
+        .by_name(name)
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap();
+    content
+}
+
+/// docx 中一个 xml 部件的内容
+fn part(docx: &Path, name: &str) -> String {
+    String::from_utf8(bytes(docx, name)).unwrap()
+}
+
+/// 复制 docx，用 `edit` 修改各部件，并加入 `extra` 中的部件
+fn repack(
+    source: &Path,
+    target: &Path,
+    edit: impl Fn(&str, Vec<u8>) -> Vec<u8>,
+    extra: &[(&str, &[u8])],
+) {
+    let mut archive = zip::ZipArchive::new(fs::File::open(source).unwrap()).unwrap();
+    let mut writer = zip::ZipWriter::new(fs::File::create(target).unwrap());
+    let options = zip::write::FileOptions::default();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).unwrap();
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).unwrap();
+        writer.start_file(file.name(), options).unwrap();
+        writer.write_all(&edit(file.name(), content)).unwrap();
+    }
+    for (name, content) in extra {
+        writer.start_file(*name, options).unwrap();
+        writer.write_all(content).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+#[test]
+fn converts_with_unused_quote_style() {
+    let dir = workspace(
+        "unused-quote",
+        &[
+            ("a.md", "# T\n\ntext\n"),
+            ("s.toml", "[Quote]\nleft-indent = 3\n"),
+        ],
+    );
+    convert(&dir, &["-s", "s.toml", "a.md"]);
+    assert!(part(&dir.join("a.docx"), "word/document.xml").contains("text"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn grid_uses_body_font_size() {
+    let dir = workspace(
+        "grid",
+        &[
+            ("a.md", "text\n"),
+            ("f.toml", "[BodyText]\nfont-size = \"三号\"\n"),
+            (
+                "g.toml",
+                "[page]\ngrid = \"lines-and-chars\"\nlines-per-page = 22\nchars-per-line = 28\n",
+            ),
+        ],
+    );
+    convert(&dir, &["-s", "f.toml", "-s", "g.toml", "a.md"]);
+    let document = part(&dir.join("a.docx"), "word/document.xml");
+    assert!(document.contains(r#"w:charSpace="-3335""#), "{document}");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn renames_styles_in_reference_headers() {
+    let dir = workspace(
+        "header-rename",
+        &[
+            ("a.md", "text\n"),
+            ("h.toml", "[header]\ntext = \"Head {page}\"\n"),
+        ],
+    );
+    convert(&dir, &["-s", "h.toml", "a.md"]);
+
+    // 参考文档中页眉样式的 id 与内置样式不同，如中文版 Word 中的 `a3`
+    let source = dir.join("a.docx");
+    let rename = |name: &str, content: Vec<u8>| match name {
+        "word/styles.xml" => String::from_utf8(content)
+            .unwrap()
+            .replace(r#"w:styleId="Header""#, r#"w:styleId="a3""#)
+            .into_bytes(),
+        _ => content,
+    };
+    repack(&source, &dir.join("tpl.docx"), rename, &[]);
+    fs::remove_file(source).unwrap();
+
+    convert(&dir, &["-s", "h.toml", "-r", "tpl.docx", "a.md"]);
+    let header = part(&dir.join("a.docx"), "word/md2docx-header1.xml");
+    assert!(header.contains(r#""a3""#), "{header}");
+    assert!(!header.contains(r#""Header""#), "{header}");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn keeps_binary_parts_of_reference_and_template() {
+    let dir = workspace(
+        "binary-parts",
+        &[
+            ("a.md", "text\n"),
+            ("tpl.md", "before\n\n\\{{content}}\n\nafter\n"),
+        ],
+    );
+    convert(&dir, &["tpl.md"]);
+
+    // 不是合法 UTF-8 的字节，如图片和嵌入的字体
+    let font = (0..=255).rev().collect::<Vec<u8>>();
+    let name = "word/fonts/font1.odttf";
+    repack(
+        &dir.join("tpl.docx"),
+        &dir.join("ref.docx"),
+        |_, content| content,
+        &[(name, &font)],
+    );
+    convert(&dir, &["-r", "ref.docx", "a.md"]);
+    assert_eq!(bytes(&dir.join("a.docx"), name), font);
+    fs::remove_file(dir.join("a.docx")).unwrap();
+
+    convert(&dir, &["-t", "ref.docx", "a.md"]);
+    assert_eq!(bytes(&dir.join("a.docx"), name), font);
+    assert!(part(&dir.join("a.docx"), "word/document.xml").contains("after"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// 正文节属性中各类页眉或页脚引用的部件中的文字
+fn referenced_texts(docx: &Path, kind: &str) -> Vec<(String, String)> {
+    let document = part(docx, "word/document.xml");
+    let rels = part(docx, "word/_rels/document.xml.rels");
+    let attr = |element: &str, name: &str| {
+        let start = element.find(&format!(" {name}=\"")).unwrap() + name.len() + 3;
+        element[start..start + element[start..].find('"').unwrap()].to_string()
+    };
+    document
+        .split(&format!("<w:{kind}Reference "))
+        .skip(1)
+        .map(|reference| {
+            let reference = &format!(" {}", &reference[..reference.find('>').unwrap()]);
+            let id = attr(reference, "r:id");
+            let relationship = rels
+                .split("<Relationship ")
+                .find(|r| r.contains(&format!(r#"Id="{id}""#)))
+                .unwrap();
+            let target = part(docx, &format!("word/{}", attr(relationship, "Target")));
+            let text = target
+                .split("<w:t")
+                .skip(1)
+                .map(|t| &t[t.find('>').unwrap() + 1..t.find('<').unwrap()])
+                .collect();
+            (attr(reference, "w:type"), text)
+        })
+        .collect()
+}
+
+#[test]
+fn links_header_and_footer_variants() {
+    let dir = workspace(
+        "variants",
+        &[
+            ("a.md", "text\n"),
+            (
+                "s.toml",
+                "[header.even]\ntext = \"EVEN HEAD\"\n\n[footer]\ntext = \"DEFAULT\"\n\n[footer.first]\ntext = \"FIRST\"\n\n[footer.even]\ntext = \"EVEN\"\n",
+            ),
+        ],
+    );
+    convert(&dir, &["-s", "s.toml", "a.md"]);
+    let docx = dir.join("a.docx");
+    let expected = |pairs: &[(&str, &str)]| {
+        pairs
+            .iter()
+            .map(|&(kind, text)| (kind.to_string(), text.to_string()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        referenced_texts(&docx, "footer"),
+        expected(&[("default", "DEFAULT"), ("first", "FIRST"), ("even", "EVEN")])
+    );
+    assert_eq!(
+        referenced_texts(&docx, "header"),
+        expected(&[("default", ""), ("first", ""), ("even", "EVEN HEAD")])
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn sets_title_page_on_first_section_only() {
+    let dir = workspace(
+        "title-page",
+        &[
+            ("a.md", "a\n\n::: landscape\nb\n:::\n\nc\n"),
+            (
+                "s.toml",
+                "[footer]\ntext = \"{page}\"\n\n[footer.first]\ntext = \"\"\n",
+            ),
+        ],
+    );
+    convert(&dir, &["-s", "s.toml", "a.md"]);
+    let document = part(&dir.join("a.docx"), "word/document.xml");
+    let sections = document.split("<w:sectPr").skip(1).collect::<Vec<_>>();
+    assert_eq!(sections.len(), 3);
+    let title_pages = sections
+        .iter()
+        .map(|s| s[..s.find("</w:sectPr>").unwrap()].contains("<w:titlePg"))
+        .collect::<Vec<_>>();
+    assert_eq!(title_pages, [true, false, false]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn gbt9704_uses_standard_type_area() {
+    let dir = workspace("gbt9704", &[("a.md", "text\n")]);
+    convert(&dir, &["-p", "gbt9704", "a.md"]);
+    let document = part(&dir.join("a.docx"), "word/document.xml");
+    // 版心 156 mm × 225 mm，每页 22 行，每行 28 个三号字
+    assert!(
+        document.contains(r#"w:top="2098" w:right="1474" w:bottom="1984" w:left="1587""#),
+        "{document}"
+    );
+    assert!(
+        document.contains(r#"w:linePitch="579" w:charSpace="-841""#),
+        "{document}"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn styles_line_numbers_in_both_layouts() {
+    let dir = workspace(
+        "line-numbers",
+        &[
+            ("a.md", "```rust linenos\nfn a() {}\n```\n"),
+            ("p.toml", "[Code]\nlayout = \"paragraphs\"\n"),
+        ],
+    );
+    for args in [&["a.md"][..], &["-s", "p.toml", "a.md"]] {
+        convert(&dir, args);
+        let docx = dir.join("a.docx");
+        let document = part(&docx, "word/document.xml");
+        assert!(
+            document.contains(
+                r#"<w:rStyle w:val="CodeLineNumber" /></w:rPr><w:t xml:space="preserve">1"#
+            ),
+            "{document}"
+        );
+        assert!(!document.contains("808080"), "{document}");
+        fs::remove_file(docx).unwrap();
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn keeps_diff_styles_on_highlighted_lines() {
+    let dir = workspace(
+        "diff-highlight",
+        &[
+            ("a.md", "```diff hl=2\n@@ -1 +1 @@\n-old\n+new\n```\n"),
+            ("p.toml", "[Code]\nlayout = \"paragraphs\"\n"),
+        ],
+    );
+    for args in [&["a.md"][..], &["-s", "p.toml", "a.md"]] {
+        convert(&dir, args);
+        let docx = dir.join("a.docx");
+        let document = part(&docx, "word/document.xml");
+        let line = document
+            .split("<w:p>")
+            .find(|p| p.contains(">-old<"))
+            .unwrap();
+        assert!(
+            line.contains(r#"<w:pStyle w:val="DiffDelete" />"#),
+            "{line}"
+        );
+        assert!(line.contains(r#"w:fill="FFF2CC""#), "{line}");
+        assert!(!document.contains("md2docx-highlighted-"), "{document}");
+        fs::remove_file(docx).unwrap();
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,30 @@
+//! 运行 `md2docx show`，检查输出的语法树
+
+use std::{fs, process::Command};
+
+#[test]
+fn shows_directives_and_html() {
+    let dir = std::env::temp_dir().join(format!("md2docx-show-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("a.md");
+    fs::write(&file, "<!-- mainmatter -->\n\na  \nb <br>\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_md2docx"))
+        .arg("show")
+        .arg(&file)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains(r#"Html: "<!-- mainmatter -->""#),
+        "{stdout}"
+    );
+    assert!(stdout.contains("Break"), "{stdout}");
+    assert!(stdout.contains(r#"Html: "<br>""#), "{stdout}");
+    fs::remove_dir_all(dir).unwrap();
+}